@@ -0,0 +1,592 @@
+use serde::Deserialize;
+use std::ffi::CStr;
+use std::fmt;
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+#[cfg(any(test, feature = "mock-auth"))]
+use std::time::{Duration, Instant};
+
+use crate::pam;
+use crate::secret::Secret;
+use crate::settings::AuthSettings;
+use crate::shadow;
+
+#[derive(Debug)]
+pub enum AuthError {
+    Pam(pam::PamError),
+    Shadow(shadow::ShadowError),
+    // Running sudo failed
+    Io(std::io::Error),
+    Disconnected,
+}
+
+/// What went wrong, in terms the lock screen can show
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AuthErrorKind {
+    // The backend's library or program is missing or can't be read
+    Unavailable,
+    // The backend runs but is set up wrong, like a broken PAM stack
+    Misconfigured,
+    NoUser,
+    // The password was right but the account is expired or locked
+    Account,
+    Internal,
+}
+
+impl AuthErrorKind {
+    /// Short enough to fit above the ring
+    pub fn message(&self) -> &'static str {
+        match self {
+            AuthErrorKind::Unavailable => "authenticator unavailable",
+            AuthErrorKind::Misconfigured => "authentication misconfigured",
+            AuthErrorKind::NoUser => "unknown user",
+            AuthErrorKind::Account => "account expired or locked",
+            AuthErrorKind::Internal => "authentication failed to run",
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            AuthErrorKind::Unavailable => "unavailable",
+            AuthErrorKind::Misconfigured => "misconfigured",
+            AuthErrorKind::NoUser => "no-user",
+            AuthErrorKind::Account => "account",
+            AuthErrorKind::Internal => "internal",
+        }
+    }
+}
+
+impl AuthError {
+    pub fn kind(&self) -> AuthErrorKind {
+        use pam::PamError;
+        use shadow::ShadowError;
+        match self {
+            AuthError::Pam(PamError::Load(_))
+            | AuthError::Shadow(ShadowError::Load(_) | ShadowError::Read(_)) => {
+                AuthErrorKind::Unavailable
+            }
+            AuthError::Io(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                AuthErrorKind::Unavailable
+            }
+            AuthError::Pam(PamError::Pam { .. })
+            | AuthError::Shadow(ShadowError::NoPassword(_) | ShadowError::Crypt) => {
+                AuthErrorKind::Misconfigured
+            }
+            AuthError::Pam(PamError::NoUser)
+            | AuthError::Shadow(ShadowError::NoUser | ShadowError::NoEntry(_)) => {
+                AuthErrorKind::NoUser
+            }
+            AuthError::Pam(PamError::Account { .. }) => AuthErrorKind::Account,
+            AuthError::Shadow(ShadowError::Privileges(_))
+            | AuthError::Io(_)
+            | AuthError::Disconnected => AuthErrorKind::Internal,
+        }
+    }
+
+    fn backend(&self) -> &'static str {
+        match self {
+            AuthError::Pam(_) => "pam",
+            AuthError::Shadow(_) => "shadow",
+            AuthError::Io(_) => "sudo",
+            AuthError::Disconnected => "unknown",
+        }
+    }
+}
+
+/// Reports a failed check as one `key=value` line on stderr, which the
+/// journal picks up when raylock runs as a user service
+pub fn log_error(e: &AuthError) {
+    eprintln!(
+        "raylock: auth_error backend={} kind={} message={:?}",
+        e.backend(),
+        e.kind().name(),
+        e.to_string()
+    );
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthError::Pam(e) => write!(f, "{}", e),
+            AuthError::Shadow(e) => write!(f, "{}", e),
+            AuthError::Io(e) => write!(f, "{}", e),
+            AuthError::Disconnected => write!(f, "the verification thread stopped"),
+        }
+    }
+}
+
+impl std::error::Error for AuthError {}
+
+impl From<std::io::Error> for AuthError {
+    fn from(e: std::io::Error) -> Self {
+        AuthError::Io(e)
+    }
+}
+
+/// `Ok(true)` for a correct password, `Ok(false)` for a wrong one and
+/// `Err` when the backend couldn't answer at all.
+pub type Verdict = Result<bool, AuthError>;
+
+/// A password check that may take a while. `submit` starts a check and
+/// returns straight away, `result` is polled until the verdict is ready.
+pub trait Authenticator: Send {
+    fn submit(&mut self, password: &Secret);
+    fn result(&mut self) -> Option<Verdict>;
+}
+
+/// A blocking password check, run on its own thread by `Threaded`.
+pub trait Verify: Send + Sync + 'static {
+    fn verify(&self, password: &Secret) -> Verdict;
+}
+
+pub struct Threaded<V: Verify> {
+    verifier: Arc<V>,
+    pending: Option<Receiver<Verdict>>,
+}
+
+impl<V: Verify> Threaded<V> {
+    pub fn new(verifier: V) -> Self {
+        Threaded {
+            verifier: Arc::new(verifier),
+            pending: None,
+        }
+    }
+}
+
+impl<V: Verify> Authenticator for Threaded<V> {
+    fn submit(&mut self, password: &Secret) {
+        if self.pending.is_some() {
+            return;
+        }
+
+        let (tx, rx) = mpsc::channel();
+        let verifier = self.verifier.clone();
+        // Wiped when the thread is done with it
+        let password = password.clone();
+        thread::spawn(move || {
+            let _ = tx.send(verifier.verify(&password));
+        });
+        self.pending = Some(rx);
+    }
+
+    fn result(&mut self) -> Option<Verdict> {
+        let verdict = match self.pending.as_ref()?.try_recv() {
+            Ok(verdict) => verdict,
+            Err(TryRecvError::Empty) => return None,
+            Err(TryRecvError::Disconnected) => Err(AuthError::Disconnected),
+        };
+        self.pending = None;
+        Some(verdict)
+    }
+}
+
+pub struct PamVerifier {
+    pub service: String,
+}
+
+impl Verify for PamVerifier {
+    fn verify(&self, password: &Secret) -> Verdict {
+        pam::authenticate(&self.service, password).map_err(AuthError::Pam)
+    }
+}
+
+pub struct SudoVerifier;
+
+impl Verify for SudoVerifier {
+    fn verify(&self, password: &Secret) -> Verdict {
+        let mut child = Command::new("sudo")
+            .args(["-kS", "true"]) // Use -S to read password from stdin
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            use std::io::Write;
+            // Straight from the buffer, formatting would leave copies around
+            stdin.write_all(password.as_str().as_bytes())?;
+            stdin.write_all(b"\n")?;
+        }
+
+        Ok(child.wait()?.success())
+    }
+}
+
+pub struct ShadowVerifier {
+    hash: String,
+}
+
+impl ShadowVerifier {
+    /// Checks against `hash`, from `shadow::read_own_hash`
+    pub fn new(hash: String) -> Self {
+        ShadowVerifier { hash }
+    }
+}
+
+impl Verify for ShadowVerifier {
+    fn verify(&self, password: &Secret) -> Verdict {
+        shadow::check(password, &self.hash).map_err(AuthError::Shadow)
+    }
+}
+
+/// Compares against a password held in memory. Used for testing the
+/// lock/unlock flow without real credentials, so only built for tests and
+/// with the mock-auth feature.
+#[cfg(any(test, feature = "mock-auth"))]
+pub struct MockAuthenticator {
+    password: String,
+    delay: Duration,
+    pending: Option<(bool, Instant)>,
+}
+
+#[cfg(any(test, feature = "mock-auth"))]
+impl MockAuthenticator {
+    pub fn new(password: String, delay: Duration) -> Self {
+        MockAuthenticator {
+            password,
+            delay,
+            pending: None,
+        }
+    }
+}
+
+#[cfg(any(test, feature = "mock-auth"))]
+impl Authenticator for MockAuthenticator {
+    fn submit(&mut self, password: &Secret) {
+        if self.pending.is_none() {
+            self.pending = Some((
+                password.as_str() == self.password,
+                Instant::now() + self.delay,
+            ));
+        }
+    }
+
+    fn result(&mut self) -> Option<Verdict> {
+        let (correct, ready_at) = self.pending?;
+        if Instant::now() < ready_at {
+            return None;
+        }
+        self.pending = None;
+        Some(Ok(correct))
+    }
+}
+
+/// Asks `primary` first and `fallback` whenever `primary` can't answer. A
+/// wrong password from `primary` is final.
+pub struct Fallback {
+    primary: Box<dyn Authenticator>,
+    fallback: Box<dyn Authenticator>,
+    // Kept until `primary` answers in case `fallback` needs it
+    pending: Option<Secret>,
+    on_fallback: bool,
+}
+
+impl Authenticator for Fallback {
+    fn submit(&mut self, password: &Secret) {
+        if self.pending.is_some() {
+            return;
+        }
+        self.pending = Some(password.clone());
+        self.primary.submit(password);
+    }
+
+    fn result(&mut self) -> Option<Verdict> {
+        if self.on_fallback {
+            let verdict = self.fallback.result()?;
+            self.on_fallback = false;
+            self.pending = None;
+            return Some(verdict);
+        }
+
+        let verdict = self.primary.result()?;
+        match (verdict, self.pending.take()) {
+            (Err(e), Some(password)) => {
+                log_error(&e);
+                self.fallback.submit(&password);
+                self.pending = Some(password);
+                self.on_fallback = true;
+                None
+            }
+            (verdict, _) => Some(verdict),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    #[default]
+    Pam,
+    Sudo,
+    Shadow,
+    #[cfg(feature = "mock-auth")]
+    Mock,
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pam" => Ok(Backend::Pam),
+            "sudo" => Ok(Backend::Sudo),
+            "shadow" => Ok(Backend::Shadow),
+            #[cfg(feature = "mock-auth")]
+            "mock" => Ok(Backend::Mock),
+            _ => Err(format!(
+                "unknown auth backend '{}' (expected pam, sudo or shadow)",
+                s
+            )),
+        }
+    }
+}
+
+/// Builds the configured backend and its fallback. If only one of them can
+/// be set up it's used alone. `shadow_hash` is what `shadow::read_own_hash`
+/// returned while raylock still had the privileges to read /etc/shadow.
+pub fn create(
+    settings: &AuthSettings,
+    shadow_hash: Result<String, shadow::ShadowError>,
+) -> Result<Box<dyn Authenticator>, AuthError> {
+    let mut shadow_hash = Some(shadow_hash);
+    let primary = create_backend(settings.backend, settings, &mut shadow_hash);
+    let Some(fallback_backend) = settings.fallback else {
+        return primary;
+    };
+    match (
+        primary,
+        create_backend(fallback_backend, settings, &mut shadow_hash),
+    ) {
+        (Ok(primary), Ok(fallback)) => Ok(Box::new(Fallback {
+            primary,
+            fallback,
+            pending: None,
+            on_fallback: false,
+        })),
+        (Ok(primary), Err(e)) => {
+            eprintln!(
+                "raylock: could not set up the {:?} fallback, continuing without it: {}",
+                fallback_backend, e
+            );
+            Ok(primary)
+        }
+        (Err(e), Ok(fallback)) => {
+            eprintln!(
+                "raylock: could not set up {:?} authentication, using the {:?} fallback: {}",
+                settings.backend, fallback_backend, e
+            );
+            Ok(fallback)
+        }
+        (Err(e), Err(_)) => Err(e),
+    }
+}
+
+fn create_backend(
+    backend: Backend,
+    settings: &AuthSettings,
+    shadow_hash: &mut Option<Result<String, shadow::ShadowError>>,
+) -> Result<Box<dyn Authenticator>, AuthError> {
+    Ok(match backend {
+        Backend::Pam => Box::new(Threaded::new(PamVerifier {
+            service: settings.pam_service.clone(),
+        })),
+        Backend::Sudo => Box::new(Threaded::new(SudoVerifier)),
+        Backend::Shadow => {
+            let hash = shadow_hash
+                .take()
+                .unwrap_or_else(shadow::read_own_hash)
+                .map_err(AuthError::Shadow)?;
+            Box::new(Threaded::new(ShadowVerifier::new(hash)))
+        }
+        #[cfg(feature = "mock-auth")]
+        Backend::Mock => Box::new(MockAuthenticator::new(
+            settings.mock_password.clone(),
+            Duration::from_millis(settings.mock_delay_ms),
+        )),
+    })
+}
+
+pub fn current_user() -> Option<String> {
+    let passwd = unsafe { libc::getpwuid(libc::getuid()) };
+    if passwd.is_null() {
+        return None;
+    }
+    let name = unsafe { CStr::from_ptr((*passwd).pw_name) };
+    Some(name.to_string_lossy().into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    // Answers with `answer` once something is sent on `release`, so a check
+    // can be seen pending
+    struct Gated {
+        answer: fn(&Secret) -> Verdict,
+        release: Mutex<Receiver<()>>,
+    }
+
+    impl Verify for Gated {
+        fn verify(&self, password: &Secret) -> Verdict {
+            let _ = self.release.lock().unwrap().recv();
+            (self.answer)(password)
+        }
+    }
+
+    fn gated(answer: fn(&Secret) -> Verdict) -> (Threaded<Gated>, mpsc::Sender<()>) {
+        let (release, rx) = mpsc::channel();
+        let verifier = Gated {
+            answer,
+            release: Mutex::new(rx),
+        };
+        (Threaded::new(verifier), release)
+    }
+
+    fn right_password(password: &Secret) -> Verdict {
+        Ok(password.as_str() == "right")
+    }
+
+    // Polls like the auth thread does
+    fn wait(authenticator: &mut dyn Authenticator) -> Verdict {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            if let Some(verdict) = authenticator.result() {
+                return verdict;
+            }
+            assert!(Instant::now() < deadline, "no verdict");
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn threaded_check_is_pending_until_answered() {
+        let (mut authenticator, release) = gated(right_password);
+        assert!(authenticator.result().is_none());
+        authenticator.submit(&Secret::from("right"));
+        thread::sleep(Duration::from_millis(20));
+        assert!(authenticator.result().is_none());
+        // Ignored while the first one is still being checked
+        authenticator.submit(&Secret::from("wrong"));
+        release.send(()).unwrap();
+        assert!(matches!(wait(&mut authenticator), Ok(true)));
+        assert!(authenticator.result().is_none());
+    }
+
+    #[test]
+    fn threaded_reports_wrong_passwords() {
+        let (mut authenticator, release) = gated(right_password);
+        release.send(()).unwrap();
+        authenticator.submit(&Secret::from("wrong"));
+        assert!(matches!(wait(&mut authenticator), Ok(false)));
+
+        release.send(()).unwrap();
+        authenticator.submit(&Secret::from("right"));
+        assert!(matches!(wait(&mut authenticator), Ok(true)));
+    }
+
+    #[test]
+    fn threaded_reports_unavailable_backends() {
+        let (mut authenticator, release) =
+            gated(|_| Err(std::io::Error::from(std::io::ErrorKind::NotFound).into()));
+        release.send(()).unwrap();
+        authenticator.submit(&Secret::from("right"));
+        let error = wait(&mut authenticator).unwrap_err();
+        assert_eq!(error.kind(), AuthErrorKind::Unavailable);
+    }
+
+    #[test]
+    fn threaded_reports_a_verifier_that_died() {
+        let (mut authenticator, release) = gated(|_| panic!("verifier crashed"));
+        release.send(()).unwrap();
+        authenticator.submit(&Secret::from("right"));
+        assert!(matches!(
+            wait(&mut authenticator),
+            Err(AuthError::Disconnected)
+        ));
+    }
+
+    #[test]
+    fn mock_answers_after_its_delay() {
+        let delay = Duration::from_millis(50);
+        let mut authenticator = MockAuthenticator::new("right".to_string(), delay);
+        let start = Instant::now();
+        authenticator.submit(&Secret::from("right"));
+        assert!(authenticator.result().is_none());
+        assert!(matches!(wait(&mut authenticator), Ok(true)));
+        assert!(start.elapsed() >= delay);
+
+        authenticator.submit(&Secret::from("wrong"));
+        assert!(matches!(wait(&mut authenticator), Ok(false)));
+    }
+
+    #[test]
+    fn fallback_answers_when_the_primary_cannot() {
+        let (primary, release) =
+            gated(|_| Err(std::io::Error::from(std::io::ErrorKind::NotFound).into()));
+        release.send(()).unwrap();
+        let mut authenticator = Fallback {
+            primary: Box::new(primary),
+            fallback: Box::new(MockAuthenticator::new("right".to_string(), Duration::ZERO)),
+            pending: None,
+            on_fallback: false,
+        };
+        authenticator.submit(&Secret::from("right"));
+        assert!(matches!(wait(&mut authenticator), Ok(true)));
+    }
+
+    #[test]
+    fn fallback_keeps_the_primary_verdict() {
+        let (primary, release) = gated(right_password);
+        release.send(()).unwrap();
+        let mut authenticator = Fallback {
+            primary: Box::new(primary),
+            fallback: Box::new(MockAuthenticator::new("wrong".to_string(), Duration::ZERO)),
+            pending: None,
+            on_fallback: false,
+        };
+        authenticator.submit(&Secret::from("wrong"));
+        assert!(matches!(wait(&mut authenticator), Ok(false)));
+    }
+
+    #[test]
+    fn errors_map_to_kinds() {
+        let load = unsafe { libloading::Library::new("libraylock-missing.so") }.unwrap_err();
+        let cases = [
+            (
+                AuthError::Pam(pam::PamError::Load(load)),
+                AuthErrorKind::Unavailable,
+            ),
+            (
+                AuthError::Pam(pam::PamError::Pam {
+                    code: 4,
+                    message: String::new(),
+                }),
+                AuthErrorKind::Misconfigured,
+            ),
+            (AuthError::Pam(pam::PamError::NoUser), AuthErrorKind::NoUser),
+            (
+                AuthError::Io(std::io::ErrorKind::NotFound.into()),
+                AuthErrorKind::Unavailable,
+            ),
+            (
+                AuthError::Io(std::io::ErrorKind::BrokenPipe.into()),
+                AuthErrorKind::Internal,
+            ),
+            (
+                AuthError::Shadow(shadow::ShadowError::NoEntry("alice".to_string())),
+                AuthErrorKind::NoUser,
+            ),
+            (
+                AuthError::Shadow(shadow::ShadowError::Crypt),
+                AuthErrorKind::Misconfigured,
+            ),
+            (AuthError::Disconnected, AuthErrorKind::Internal),
+        ];
+        for (error, kind) in cases {
+            assert_eq!(error.kind(), kind, "{}", error);
+        }
+    }
+}
@@ -0,0 +1,193 @@
+use serde::Deserialize;
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const RECORD_FILENAME: &str = "attempts";
+
+/// After `free_attempts` failures every further one locks input for
+/// `delay_secs`, doubling each time up to `max_delay_secs`.
+#[derive(Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BackoffSettings {
+    pub free_attempts: u16,
+    pub delay_secs: u64,
+    pub max_delay_secs: u64,
+}
+
+impl Default for BackoffSettings {
+    fn default() -> Self {
+        BackoffSettings {
+            free_attempts: 3,
+            delay_secs: 5,
+            max_delay_secs: 300,
+        }
+    }
+}
+
+impl BackoffSettings {
+    pub fn delay(&self, failures: u16) -> Duration {
+        let Some(over) = failures.checked_sub(self.free_attempts) else {
+            return Duration::ZERO;
+        };
+        if over == 0 || self.delay_secs == 0 {
+            return Duration::ZERO;
+        }
+        let doublings = u32::from(over - 1).min(32);
+        let delay = self.delay_secs.saturating_mul(1 << doublings);
+        Duration::from_secs(delay.min(self.max_delay_secs))
+    }
+}
+
+/// Kept on disk so restarting raylock doesn't reset it
+#[derive(Clone, Copy, Default)]
+pub struct Record {
+    pub failed_attempts: u16,
+    // When the lockout ends and how long it was in total
+    pub lockout: Option<(SystemTime, Duration)>,
+}
+
+impl Record {
+    pub fn locked_out(&self) -> bool {
+        self.remaining().is_some()
+    }
+
+    pub fn remaining(&self) -> Option<Duration> {
+        let (until, _) = self.lockout?;
+        until
+            .duration_since(SystemTime::now())
+            .ok()
+            .filter(|left| !left.is_zero())
+    }
+}
+
+fn record_path() -> Option<PathBuf> {
+    let dir = match env::var_os("XDG_STATE_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("HOME")?)
+            .join(".local")
+            .join("state"),
+    };
+    Some(dir.join("raylock").join(RECORD_FILENAME))
+}
+
+pub fn load() -> Record {
+    let Some(contents) = record_path().and_then(|path| fs::read_to_string(path).ok()) else {
+        return Record::default();
+    };
+    // "<failures> <lockout end, unix seconds> <lockout length, seconds>"
+    let fields: Vec<u64> = contents
+        .split_whitespace()
+        .filter_map(|field| field.parse().ok())
+        .collect();
+    match fields[..] {
+        [failures, until, length] => Record {
+            failed_attempts: failures.min(u16::MAX.into()) as u16,
+            lockout: (until > 0).then(|| {
+                (
+                    UNIX_EPOCH + Duration::from_secs(until),
+                    Duration::from_secs(length),
+                )
+            }),
+        },
+        _ => Record::default(),
+    }
+}
+
+pub fn save(record: &Record) {
+    if let Err(e) = write(record) {
+        eprintln!("raylock: could not save the failed attempts: {}", e);
+    }
+}
+
+fn write(record: &Record) -> io::Result<()> {
+    let path = record_path().ok_or(io::ErrorKind::NotFound)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let (until, length) = record.lockout.map_or((0, 0), |(until, length)| {
+        let until = until.duration_since(UNIX_EPOCH).unwrap_or_default();
+        // Rounded up so a reload never ends the lockout early
+        (until.as_secs() + 1, length.as_secs())
+    });
+
+    // Written next to it and renamed, a crash never leaves half a record
+    let tmp = path.with_extension("tmp");
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&tmp)?;
+    writeln!(file, "{} {} {}", record.failed_attempts, until, length)?;
+    file.sync_all()?;
+    fs::rename(tmp, path)
+}
+
+pub fn clear() {
+    if let Some(path) = record_path() {
+        match fs::remove_file(path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => eprintln!("raylock: could not reset the failed attempts: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delays_double_up_to_the_cap() {
+        let settings = BackoffSettings::default();
+        for failures in 0..=3 {
+            assert_eq!(settings.delay(failures), Duration::ZERO);
+        }
+        let secs = |failures| settings.delay(failures).as_secs();
+        assert_eq!([secs(4), secs(5), secs(6), secs(7)], [5, 10, 20, 40]);
+        assert_eq!(secs(10), 300);
+        assert_eq!(secs(u16::MAX), 300);
+
+        let off = BackoffSettings {
+            delay_secs: 0,
+            ..settings
+        };
+        assert_eq!(off.delay(10), Duration::ZERO);
+    }
+
+    #[test]
+    fn records_survive_a_restart() {
+        let dir = env::temp_dir().join(format!("raylock-state-{}", std::process::id()));
+        env::set_var("XDG_STATE_HOME", &dir);
+
+        let length = Duration::from_secs(60);
+        save(&Record {
+            failed_attempts: 4,
+            lockout: Some((SystemTime::now() + length, length)),
+        });
+        let record = load();
+        assert_eq!(record.failed_attempts, 4);
+        assert_eq!(record.lockout.map(|(_, length)| length), Some(length));
+        let left = record.remaining().unwrap();
+        assert!(left > Duration::from_secs(58) && left <= Duration::from_secs(61));
+
+        // A lockout that ran out while raylock wasn't running is over
+        save(&Record {
+            failed_attempts: 5,
+            lockout: Some((SystemTime::now() - length, length)),
+        });
+        let record = load();
+        assert_eq!(record.failed_attempts, 5);
+        assert!(!record.locked_out());
+
+        clear();
+        let record = load();
+        assert_eq!(record.failed_attempts, 0);
+        assert!(record.lockout.is_none());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
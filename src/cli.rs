@@ -0,0 +1,118 @@
+use std::env;
+use std::ffi::OsString;
+use std::os::fd::RawFd;
+use std::path::PathBuf;
+
+use crate::auth::Backend;
+use crate::settings::LockMode;
+
+const USAGE: &str = "Usage: raylock [OPTIONS]
+
+Options:
+  --config PATH        Config file (default: $XDG_CONFIG_HOME/raylock/config.toml)
+  --layout PATH        Pane layout (default: $XDG_CONFIG_HOME/raylock/layout.json)
+  --theme PATH         Theme file, TOML or JSON (default: $XDG_CONFIG_HOME/raylock/theme.toml)
+  --mode MODE          How to lock: window or session-lock (default: session-lock)
+  --auth BACKEND       Password check: pam, sudo or shadow (default: pam)
+  --pam-service NAME   PAM service used to check the password (default: raylock)
+  --force              Start even if another raylock holds the lock file
+  --no-grace           Ask for the password right away, ignoring grace_secs
+  --daemon             Stay running and lock whenever logind asks to, or before sleep
+  --daemonize          Fork into the background, returning once the screen is locked
+  --ready-fd FD        Write a newline to FD and close it once the screen is locked
+  --unlock-signal      Unlock on SIGUSR1 and end the grace period on SIGUSR2, how
+                       --daemon passes on logind's Unlock and sleep
+  -h, --help           Print this help";
+
+#[derive(Default)]
+pub struct Args {
+    pub config: Option<PathBuf>,
+    pub layout: Option<PathBuf>,
+    pub theme: Option<PathBuf>,
+    pub mode: Option<LockMode>,
+    pub auth: Option<Backend>,
+    pub pam_service: Option<String>,
+    pub force: bool,
+    pub no_grace: bool,
+    pub daemon: bool,
+    pub daemonize: bool,
+    pub ready_fd: Option<RawFd>,
+    pub unlock_signal: bool,
+}
+
+impl Args {
+    pub fn parse() -> Self {
+        let mut args = Args::default();
+        let mut iter = env::args().skip(1);
+
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--config" => args.config = Some(value(&arg, iter.next()).into()),
+                "--layout" => args.layout = Some(value(&arg, iter.next()).into()),
+                "--theme" => args.theme = Some(value(&arg, iter.next()).into()),
+                "--mode" => {
+                    args.mode = Some(
+                        value(&arg, iter.next())
+                            .parse()
+                            .unwrap_or_else(|e: String| usage_error(&e)),
+                    )
+                }
+                "--auth" => {
+                    args.auth = Some(
+                        value(&arg, iter.next())
+                            .parse()
+                            .unwrap_or_else(|e: String| usage_error(&e)),
+                    )
+                }
+                "--pam-service" => args.pam_service = Some(value(&arg, iter.next())),
+                "--force" => args.force = true,
+                "--no-grace" => args.no_grace = true,
+                "--daemon" => args.daemon = true,
+                "--daemonize" => args.daemonize = true,
+                "--ready-fd" => {
+                    let fd = value(&arg, iter.next());
+                    args.ready_fd = Some(fd.parse().unwrap_or_else(|_| {
+                        usage_error(&format!("'{}' is not a file descriptor", fd))
+                    }))
+                }
+                "--unlock-signal" => args.unlock_signal = true,
+                "-h" | "--help" => {
+                    println!("{}", USAGE);
+                    std::process::exit(0);
+                }
+                _ => usage_error(&format!("unknown option '{}'", arg)),
+            }
+        }
+
+        args
+    }
+}
+
+/// Our arguments without the ones about running in the background, for
+/// the lock screens `--daemon` starts
+pub fn lock_args() -> Vec<OsString> {
+    let mut lock_args = Vec::new();
+    let mut iter = env::args_os().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.to_str() {
+            Some("--daemon" | "--daemonize") => {}
+            Some("--ready-fd") => {
+                iter.next();
+            }
+            _ => lock_args.push(arg),
+        }
+    }
+    lock_args
+}
+
+fn value(flag: &str, value: Option<String>) -> String {
+    match value {
+        Some(value) => value,
+        None => usage_error(&format!("'{}' needs a value", flag)),
+    }
+}
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("raylock: {}\n\n{}", message, USAGE);
+    std::process::exit(2);
+}
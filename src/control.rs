@@ -0,0 +1,314 @@
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::UNIX_EPOCH;
+
+use crate::backoff::Record;
+use crate::events::{self, Event};
+use crate::structs::AuthState;
+
+const SOCKET_FILENAME: &str = "raylock.sock";
+const MAX_REQUEST_LEN: usize = 4096;
+// In characters
+const MAX_BANNER_LEN: usize = 200;
+
+static SOCKET: Mutex<Option<PathBuf>> = Mutex::new(None);
+// Written to without blocking
+static SUBSCRIBERS: Mutex<Vec<UnixStream>> = Mutex::new(Vec::new());
+
+/// One request per line, like `{"command": "status"}`
+#[derive(Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case", deny_unknown_fields)]
+enum Request {
+    Status,
+    // The connection takes no more requests after this
+    Subscribe,
+    // No text clears it
+    Message { text: Option<String> },
+    EndGrace,
+}
+
+pub fn socket_path() -> PathBuf {
+    match env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir).join(SOCKET_FILENAME),
+        None => env::temp_dir().join(format!("raylock-{}.sock", unsafe { libc::getuid() })),
+    }
+}
+
+/// Only call with the instance lock held, a socket already at the path is
+/// taken to be left over and replaced
+pub fn start(state: Arc<Mutex<AuthState>>) -> io::Result<()> {
+    let path = socket_path();
+    match fs::remove_file(&path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    let listener = UnixListener::bind(&path)?;
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+    *SOCKET.lock().unwrap() = Some(path);
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    eprintln!("raylock: control socket: {}", e);
+                    continue;
+                }
+            };
+            if let Err(e) = check_peer(&stream, unsafe { libc::getuid() }) {
+                eprintln!("raylock: control socket: {}", e);
+                continue;
+            }
+            let state = state.clone();
+            thread::spawn(move || {
+                if let Err(e) = serve(stream, &state) {
+                    eprintln!("raylock: control connection: {}", e);
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+// The socket file is 0600 already, this also covers a descriptor passed on
+// to someone else
+fn check_peer(stream: &UnixStream, uid: libc::uid_t) -> io::Result<()> {
+    let peer = peer_uid(stream)?;
+    if peer != uid {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("refused a connection from uid {}", peer),
+        ));
+    }
+    Ok(())
+}
+
+fn peer_uid(stream: &UnixStream) -> io::Result<libc::uid_t> {
+    let mut cred: libc::ucred = unsafe { std::mem::zeroed() };
+    let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+    let result = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            (&mut cred as *mut libc::ucred).cast(),
+            &mut len,
+        )
+    };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(cred.uid)
+}
+
+fn serve(stream: UnixStream, state: &Mutex<AuthState>) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    loop {
+        let mut line = String::new();
+        let read = (&mut reader)
+            .take(MAX_REQUEST_LEN as u64)
+            .read_line(&mut line)?;
+        if read == 0 {
+            return Ok(());
+        }
+        if read == MAX_REQUEST_LEN && !line.ends_with('\n') {
+            let error = format!("request longer than {} bytes", MAX_REQUEST_LEN);
+            return send(&mut writer, &json!({ "error": error }));
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+        let reply = match serde_json::from_str::<Request>(&line) {
+            Ok(Request::Status) => status(&state.lock().unwrap().attempts()),
+            Ok(Request::Subscribe) => {
+                send(&mut writer, &json!({ "ok": true }))?;
+                writer.set_nonblocking(true)?;
+                SUBSCRIBERS.lock().unwrap().push(writer);
+                return Ok(());
+            }
+            Ok(Request::Message { text }) => match text {
+                Some(text) if text.chars().count() > MAX_BANNER_LEN => {
+                    let error = format!("message longer than {} characters", MAX_BANNER_LEN);
+                    json!({ "error": error })
+                }
+                text => {
+                    state.lock().unwrap().banner = text.filter(|text| !text.trim().is_empty());
+                    json!({ "ok": true })
+                }
+            },
+            Ok(Request::EndGrace) => {
+                state.lock().unwrap().grace = None;
+                json!({ "ok": true })
+            }
+            Err(e) => json!({ "error": e.to_string() }),
+        };
+        send(&mut writer, &reply)?;
+    }
+}
+
+fn send(stream: &mut UnixStream, value: &Value) -> io::Result<()> {
+    let mut line = value.to_string();
+    line.push('\n');
+    stream.write_all(line.as_bytes())
+}
+
+fn status(attempts: &Record) -> Value {
+    let locked_since = events::locked_at()
+        .and_then(|at| at.duration_since(UNIX_EPOCH).ok())
+        .map(|since| since.as_secs());
+    json!({
+        "locked": locked_since.is_some(),
+        "locked_since": locked_since,
+        "failed_attempts": attempts.failed_attempts,
+        "lockout_secs": attempts.remaining().map(|left| left.as_secs() + 1),
+    })
+}
+
+/// A subscriber that isn't keeping up or has gone away is dropped rather
+/// than waited on
+pub fn publish(event: Event, attempts: &Record) {
+    let mut message = status(attempts);
+    message["event"] = event.name().into();
+    let mut subscribers = SUBSCRIBERS.lock().unwrap();
+    subscribers.retain_mut(|subscriber| send(subscriber, &message).is_ok());
+}
+
+/// Client side, for a second instance started with --no-grace
+pub fn end_grace() -> io::Result<()> {
+    let mut stream = UnixStream::connect(socket_path())?;
+    send(&mut stream, &json!({ "command": "end_grace" }))?;
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply)?;
+    match serde_json::from_str::<Value>(&reply) {
+        Ok(reply) if reply["ok"] == true => Ok(()),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unexpected reply {:?}", reply.trim()),
+        )),
+    }
+}
+
+pub fn close() {
+    if let Some(path) = SOCKET.lock().unwrap_or_else(|e| e.into_inner()).take() {
+        let _ = fs::remove_file(path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    // Sends `requests` over a connection served like a real one, returns
+    // the replies
+    fn exchange(state: &Arc<Mutex<AuthState>>, requests: &str) -> Vec<Value> {
+        let (mut ours, theirs) = UnixStream::pair().unwrap();
+        let state = state.clone();
+        let server = thread::spawn(move || serve(theirs, &state));
+        ours.write_all(requests.as_bytes()).unwrap();
+        ours.shutdown(std::net::Shutdown::Write).unwrap();
+        let replies = BufReader::new(ours)
+            .lines()
+            .map(|line| serde_json::from_str(&line.unwrap()).unwrap())
+            .collect();
+        server.join().unwrap().unwrap();
+        replies
+    }
+
+    #[test]
+    fn end_grace_asks_for_the_password() {
+        let state = Arc::new(Mutex::new(AuthState {
+            grace: Some((Instant::now(), Duration::from_secs(30))),
+            ..Default::default()
+        }));
+        let replies = exchange(&state, "{\"command\": \"end_grace\"}\n");
+        assert_eq!(replies, [json!({ "ok": true })]);
+        assert!(state.lock().unwrap().grace.is_none());
+    }
+
+    #[test]
+    fn messages_set_and_clear_the_banner() {
+        let state = Arc::new(Mutex::new(AuthState::default()));
+        exchange(
+            &state,
+            "{\"command\": \"message\", \"text\": \"Back at 3pm\"}\n",
+        );
+        assert_eq!(state.lock().unwrap().banner.as_deref(), Some("Back at 3pm"));
+        exchange(&state, "{\"command\": \"message\"}\n");
+        assert!(state.lock().unwrap().banner.is_none());
+    }
+
+    #[test]
+    fn bad_requests_get_an_error() {
+        let state = Arc::new(Mutex::new(AuthState::default()));
+        let replies = exchange(&state, "{\"command\": \"unlock\"}\n");
+        assert!(replies[0]["error"].is_string(), "{:?}", replies);
+    }
+
+    #[test]
+    fn long_requests_are_refused() {
+        let state = Arc::new(Mutex::new(AuthState::default()));
+        let text = "x".repeat(MAX_BANNER_LEN + 1);
+        let request = json!({ "command": "message", "text": text }).to_string() + "\n";
+        let replies = exchange(&state, &request);
+        assert!(replies[0]["error"].is_string(), "{:?}", replies);
+        assert!(state.lock().unwrap().banner.is_none());
+
+        // The connection ends at the first overlong line
+        let (mut ours, theirs) = UnixStream::pair().unwrap();
+        let requests = "x".repeat(MAX_REQUEST_LEN * 2) + "\n{\"command\": \"status\"}\n";
+        ours.write_all(requests.as_bytes()).unwrap();
+        serve(theirs, &state).unwrap();
+        // Unread requests make the close a reset, which ends the replies too
+        let replies: Vec<Value> = BufReader::new(ours)
+            .lines()
+            .map_while(Result::ok)
+            .map(|line| serde_json::from_str(&line).unwrap())
+            .collect();
+        assert_eq!(replies.len(), 1);
+        assert!(replies[0]["error"].is_string(), "{:?}", replies);
+    }
+
+    #[test]
+    fn subscribers_get_each_event() {
+        let (ours, theirs) = UnixStream::pair().unwrap();
+        let state = Mutex::new(AuthState::default());
+        let mut writer = ours.try_clone().unwrap();
+        writer.write_all(b"{\"command\": \"subscribe\"}\n").unwrap();
+        serve(theirs, &state).unwrap();
+
+        let mut lines = BufReader::new(ours).lines();
+        let mut next = || serde_json::from_str::<Value>(&lines.next().unwrap().unwrap()).unwrap();
+        assert_eq!(next(), json!({ "ok": true }));
+
+        publish(
+            Event::FailedAttempt,
+            &Record {
+                failed_attempts: 2,
+                lockout: None,
+            },
+        );
+        let event = next();
+        assert_eq!(event["event"], "failed_attempt");
+        assert_eq!(event["failed_attempts"], 2);
+    }
+
+    #[test]
+    fn other_users_are_refused() {
+        let (ours, _theirs) = UnixStream::pair().unwrap();
+        let uid = unsafe { libc::getuid() };
+        assert!(check_peer(&ours, uid).is_ok());
+        let refused = check_peer(&ours, uid + 1).unwrap_err();
+        assert_eq!(refused.kind(), io::ErrorKind::PermissionDenied);
+    }
+}
@@ -1,56 +1,47 @@
-use egui::{Color32, Pos2, Rect, Stroke};
-use std::collections::VecDeque;
-use std::time::Instant;
-use sysinfo::System;
+use egui::Rect;
 
-use crate::configs;
 use crate::graph::*;
+use crate::sampler::{self, Metric};
+use crate::theme::GraphTheme;
 
 pub struct CpuGraph {
-    sys: System,
     cpu_graph: ResourceGraph,
-    last_update: Instant,
+    // Sample the graph last took a point from
+    seen: u64,
 }
 
 impl CpuGraph {
     pub fn new() -> Self {
-        let mut sys = System::new();
-        sys.refresh_cpu_all();
+        sampler::get().want(Metric::Cpu);
 
-        let mut cpu_graph =
-            ResourceGraph::new("CPU Usage".to_string(), "%".to_string(), 0.0, 100.0);
+        let cpu_graph = ResourceGraph::new("CPU Usage".to_string(), "%".to_string(), 0.0, 100.0);
 
-        let cpu_count = sys.cpus().len();
-        for i in 0..cpu_count {
-            let hue = (i as f32 / cpu_count as f32) * 360.0;
-            cpu_graph.add_line(format!("CPU {}", i), configs::GRAPH_STROKE);
-        }
-
-        Self {
-            sys,
-            cpu_graph,
-            last_update: Instant::now(),
-        }
+        Self { cpu_graph, seen: 0 }
     }
 
     pub fn update(&mut self) {
-        let now = Instant::now();
-        if now.duration_since(self.last_update).as_secs_f32() < 1.0 / UPDATES_PER_SECOND {
+        let sample = sampler::get().cpu.load();
+        if sample.seq == self.seen {
             return;
         }
 
-        self.sys.refresh_cpu_all();
-        // self.sys.refresh_memory();
-
-        // Update CPU usage
-        for (i, cpu) in self.sys.cpus().iter().enumerate() {
-            self.cpu_graph.update_line(i, cpu.cpu_usage());
+        // One line per core, added once the first sample says how many
+        for (i, usage) in sample.value.iter().enumerate() {
+            if i == self.cpu_graph.line_count() {
+                self.cpu_graph
+                    .add_line(format!("CPU {}", i), LineStyle::Plain);
+            }
+            self.cpu_graph.update_line(i, *usage);
         }
 
-        self.last_update = now;
+        self.seen = sample.seq;
+    }
+
+    pub fn animating(&self) -> bool {
+        self.cpu_graph.animating()
     }
 
-    pub fn render(&mut self, painter: &egui::Painter, rect: Rect) {
-        self.cpu_graph.render(painter, rect);
+    pub fn render(&mut self, painter: &egui::Painter, rect: Rect, theme: &GraphTheme) {
+        self.cpu_graph.render(painter, rect, theme);
     }
 }
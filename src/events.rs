@@ -0,0 +1,41 @@
+use std::sync::OnceLock;
+use std::time::SystemTime;
+
+use crate::backoff::Record;
+use crate::{control, hooks};
+
+static LOCKED_AT: OnceLock<SystemTime> = OnceLock::new();
+
+/// Things that happen to the lock, passed on to the hooks and to control
+/// socket subscribers
+#[derive(Clone, Copy)]
+pub enum Event {
+    Lock,
+    Unlock,
+    FailedAttempt,
+    Lockout,
+}
+
+impl Event {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::Lock => "lock",
+            Event::Unlock => "unlock",
+            Event::FailedAttempt => "failed_attempt",
+            Event::Lockout => "lockout",
+        }
+    }
+}
+
+pub fn emit(event: Event, attempts: &Record) {
+    if let Event::Lock = event {
+        let _ = LOCKED_AT.set(SystemTime::now());
+    }
+    hooks::run(event, attempts);
+    control::publish(event, attempts);
+}
+
+/// When the screen was locked, `None` until the lock is in place
+pub fn locked_at() -> Option<SystemTime> {
+    LOCKED_AT.get().copied()
+}
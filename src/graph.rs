@@ -1,30 +1,32 @@
-use egui::{Color32, Pos2, Rect, Stroke};
-use std::borrow::BorrowMut;
+use egui::{Pos2, Rect, Stroke};
 use std::collections::VecDeque;
 use std::time::Instant;
-use sysinfo::System;
 
-use crate::configs;
+use crate::theme::GraphTheme;
 
 pub const HISTORY_SIZE: usize = 25;
 pub const ANIMATION_DURATION: f32 = 0.2; // seconds
 pub const UPDATES_PER_SECOND: f32 = 1.5;
+pub const BYTES_PER_KB: f32 = 1024.;
+pub const BYTES_PER_MB: f32 = 1024. * 1024.;
+pub const BYTES_PER_GB: f32 = 1024. * 1024. * 1024.;
 
 #[derive(Clone)]
 struct DataPoint {
     value: f32,
+    #[allow(dead_code)]
     timestamp: Instant,
 }
 
 #[derive(Clone)]
-struct AnimatedValue {
+pub struct AnimatedValue {
     current: f32,
     target: f32,
     last_update: Instant,
 }
 
 impl AnimatedValue {
-    fn new(initial: f32) -> Self {
+    pub fn new(initial: f32) -> Self {
         Self {
             current: initial,
             target: initial,
@@ -32,12 +34,16 @@ impl AnimatedValue {
         }
     }
 
-    fn update(&mut self, new_target: f32) {
+    pub fn update(&mut self, new_target: f32) {
         self.target = new_target;
         self.last_update = Instant::now();
     }
 
-    fn get_current_value(&mut self) -> f32 {
+    pub fn animating(&self) -> bool {
+        self.last_update.elapsed().as_secs_f32() < ANIMATION_DURATION
+    }
+
+    pub fn get_current_value(&mut self) -> f32 {
         let elapsed = self.last_update.elapsed().as_secs_f32();
         let progress = (elapsed / ANIMATION_DURATION).min(1.0);
         self.current = self.current + (self.target - self.current) * progress;
@@ -45,19 +51,38 @@ impl AnimatedValue {
     }
 }
 
+// Which of the theme's graph strokes a line is drawn with
+#[derive(Clone, Copy)]
+pub enum LineStyle {
+    Plain,
+    Up,
+    Down,
+}
+
+impl LineStyle {
+    fn stroke(self, theme: &GraphTheme) -> Stroke {
+        match self {
+            LineStyle::Plain => theme.stroke,
+            LineStyle::Up => theme.up_stroke,
+            LineStyle::Down => theme.down_stroke,
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct GraphLine {
+    #[allow(dead_code)]
     label: String,
-    stroke: Stroke,
+    style: LineStyle,
     history: VecDeque<DataPoint>,
     animated_value: AnimatedValue,
 }
 
 impl GraphLine {
-    fn new(label: String, stroke: Stroke) -> Self {
+    fn new(label: String, style: LineStyle) -> Self {
         Self {
             label,
-            stroke,
+            style,
             history: VecDeque::with_capacity(HISTORY_SIZE),
             animated_value: AnimatedValue::new(0.0),
         }
@@ -79,6 +104,7 @@ impl GraphLine {
 pub struct ResourceGraph {
     lines: Vec<GraphLine>,
     // last_update: Instant,
+    #[allow(dead_code)]
     title: String,
     y_axis_label: String,
     min_value: f32,
@@ -112,8 +138,23 @@ impl ResourceGraph {
         self.max_value = max;
     }
 
-    pub fn add_line(&mut self, label: String, stroke: Stroke) {
-        self.lines.push(GraphLine::new(label, stroke));
+    pub fn set_max(&mut self, max_value: f32) {
+        self.max_value = max_value;
+    }
+
+    /// Whether a line is still sliding towards its latest value
+    pub fn animating(&self) -> bool {
+        self.lines
+            .iter()
+            .any(|line| line.animated_value.animating())
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn add_line(&mut self, label: String, style: LineStyle) {
+        self.lines.push(GraphLine::new(label, style));
     }
 
     pub fn update_line(&mut self, index: usize, value: f32) {
@@ -122,7 +163,7 @@ impl ResourceGraph {
         }
     }
 
-    pub fn render(&mut self, painter: &egui::Painter, rect: Rect) {
+    pub fn render(&mut self, painter: &egui::Painter, rect: Rect, theme: &GraphTheme) {
         // let now = Instant::now();
         // Draw background
         // painter.rect_filled(rect, 0.0, Color32::from_gray(20));
@@ -151,7 +192,7 @@ impl ResourceGraph {
                     Pos2::new(graph_rect.min.x, y),
                     Pos2::new(graph_rect.max.x, y),
                 ],
-                Stroke::new(1.0, Color32::from_gray(40)),
+                Stroke::new(1.0, theme.grid_color),
             );
 
             let value = self.max_value - (i as f32 / 4.0) * (self.max_value - self.min_value);
@@ -159,13 +200,13 @@ impl ResourceGraph {
                 Pos2::new(rect.min.x + 5.0, y - 8.0),
                 egui::Align2::LEFT_CENTER,
                 format!("{:.1}{}", value, self.y_axis_label),
-                egui::FontId::proportional(12.0),
-                Color32::LIGHT_GRAY,
+                egui::FontId::proportional(theme.label_font_size),
+                theme.label_color,
             );
         }
 
         // Draw lines
-        for (_, line) in self.lines.iter_mut().enumerate() {
+        for line in self.lines.iter_mut() {
             if line.history.is_empty() {
                 continue;
             }
@@ -194,7 +235,7 @@ impl ResourceGraph {
                 last.y = graph_rect.max.y - graph_rect.height() * normalized_value;
             }
 
-            painter.add(egui::Shape::line(animated_points, line.stroke));
+            painter.add(egui::Shape::line(animated_points, line.style.stroke(theme)));
         }
         // self.last_update = now;
     }
@@ -0,0 +1,234 @@
+use serde::Deserialize;
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, Stdio};
+use std::sync::{Mutex, OnceLock};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::backoff::Record;
+use crate::events::{self, Event};
+
+// How often a running hook is checked against its timeout
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+static HOOKS: OnceLock<HookSettings> = OnceLock::new();
+// One per running unlock hook, each gives up at the hook's timeout. Other
+// hooks aren't waited for, their watchers are detached.
+static WATCHERS: Mutex<Vec<JoinHandle<()>>> = Mutex::new(Vec::new());
+
+/// Shell commands run on lock events, each list in order of starting but
+/// all running at once
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HookSettings {
+    pub lock: Vec<String>,
+    pub unlock: Vec<String>,
+    pub failed_attempt: Vec<String>,
+    pub lockout: Vec<String>,
+    // A hook still running after this is killed
+    pub timeout_ms: u64,
+}
+
+impl Default for HookSettings {
+    fn default() -> Self {
+        HookSettings {
+            lock: Vec::new(),
+            unlock: Vec::new(),
+            failed_attempt: Vec::new(),
+            lockout: Vec::new(),
+            timeout_ms: 5000,
+        }
+    }
+}
+
+pub fn init(settings: HookSettings) {
+    let _ = HOOKS.set(settings);
+}
+
+/// Starts the hooks for `event` and returns straight away. They get
+///
+/// - `RAYLOCK_EVENT`: lock, unlock, failed_attempt or lockout
+/// - `RAYLOCK_FAILED_ATTEMPTS`: failures since the last unlock
+/// - `RAYLOCK_LOCKED_SECS`: how long the screen has been locked
+/// - `RAYLOCK_LOCKOUT_SECS`: time left on the lockout, when there is one
+pub fn run(event: Event, attempts: &Record) {
+    let Some(hooks) = HOOKS.get() else {
+        return;
+    };
+    let commands = match event {
+        Event::Lock => &hooks.lock,
+        Event::Unlock => &hooks.unlock,
+        Event::FailedAttempt => &hooks.failed_attempt,
+        Event::Lockout => &hooks.lockout,
+    };
+    let locked_for = events::locked_at()
+        .and_then(|at| at.elapsed().ok())
+        .unwrap_or_default();
+    let timeout = Duration::from_millis(hooks.timeout_ms);
+
+    for command in commands {
+        let mut shell = Command::new("sh");
+        shell
+            .args(["-c", command])
+            .env("RAYLOCK_EVENT", event.name())
+            .env(
+                "RAYLOCK_FAILED_ATTEMPTS",
+                attempts.failed_attempts.to_string(),
+            )
+            .env("RAYLOCK_LOCKED_SECS", locked_for.as_secs().to_string())
+            .stdin(Stdio::null())
+            // Its own process group, so a timeout takes whatever it started
+            // down with it
+            .process_group(0);
+        if let Some(left) = attempts.remaining() {
+            shell.env("RAYLOCK_LOCKOUT_SECS", (left.as_secs() + 1).to_string());
+        }
+
+        match shell.spawn() {
+            Ok(child) => {
+                let command = command.clone();
+                let watcher = thread::spawn(move || watch(child, &command, timeout));
+                if let Event::Unlock = event {
+                    let mut watchers = WATCHERS.lock().unwrap();
+                    watchers.retain(|watcher| !watcher.is_finished());
+                    watchers.push(watcher);
+                }
+            }
+            Err(e) => eprintln!(
+                "raylock: could not run {} hook {:?}: {}",
+                event.name(),
+                command,
+                e
+            ),
+        }
+    }
+}
+
+fn watch(mut child: Child, command: &str, timeout: Duration) {
+    let deadline = Instant::now() + timeout;
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => {
+                if !status.success() {
+                    eprintln!("raylock: hook {:?} exited with {}", command, status);
+                }
+                return;
+            }
+            Ok(None) if Instant::now() < deadline => thread::sleep(POLL_INTERVAL),
+            Ok(None) => {
+                eprintln!("raylock: hook {:?} timed out, killing it", command);
+                unsafe { libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL) };
+                let _ = child.wait();
+                return;
+            }
+            Err(e) => {
+                eprintln!("raylock: lost track of hook {:?}: {}", command, e);
+                return;
+            }
+        }
+    }
+}
+
+/// Waits for the running unlock hooks before raylock exits, at most until
+/// their timeout. Only called once the window or lock surfaces are gone,
+/// so a slow hook doesn't keep the screen covered. Lock and failed attempt
+/// hooks still running are left to finish on their own.
+pub fn finish() {
+    let watchers = std::mem::take(&mut *WATCHERS.lock().unwrap());
+    for watcher in watchers {
+        let _ = watcher.join();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::time::SystemTime;
+
+    const PATIENCE: Duration = Duration::from_secs(5);
+
+    // HOOKS is set once for the whole process, both tests share it
+    fn hooks() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("raylock-hooks-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        init(HookSettings {
+            lock: vec![format!(
+                "env > {0}/env.tmp && mv {0}/env.tmp {0}/env",
+                dir.display()
+            )],
+            // Leaves a grandchild behind, which has to go too
+            unlock: vec![format!("sleep 60 & echo $! > {}/pid; wait", dir.display())],
+            timeout_ms: 300,
+            ..HookSettings::default()
+        });
+        dir
+    }
+
+    fn wait_for(path: &Path) -> String {
+        let deadline = Instant::now() + PATIENCE;
+        while Instant::now() < deadline {
+            if let Ok(contents) = fs::read_to_string(path) {
+                if !contents.is_empty() {
+                    return contents;
+                }
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+        panic!("{} never showed up", path.display());
+    }
+
+    // Zombies count as gone, reaping them is up to init
+    fn running(pid: &str) -> bool {
+        fs::read_to_string(format!("/proc/{}/stat", pid)).is_ok_and(|stat| {
+            let state = stat.rsplit(')').next().unwrap_or_default();
+            !state.trim_start().starts_with('Z')
+        })
+    }
+
+    #[test]
+    fn hung_hooks_are_killed_with_their_children() {
+        let dir = hooks();
+        let started = Instant::now();
+        run(Event::Unlock, &Record::default());
+        finish();
+        assert!(started.elapsed() < PATIENCE);
+
+        let pid = wait_for(&dir.join("pid"));
+        let deadline = Instant::now() + PATIENCE;
+        while running(pid.trim()) && Instant::now() < deadline {
+            thread::sleep(POLL_INTERVAL);
+        }
+        assert!(
+            !running(pid.trim()),
+            "sleep {} survived the timeout",
+            pid.trim()
+        );
+    }
+
+    #[test]
+    fn hooks_get_the_event_in_their_environment() {
+        let dir = hooks();
+        let lockout = Duration::from_secs(30);
+        run(
+            Event::Lock,
+            &Record {
+                failed_attempts: 2,
+                lockout: Some((SystemTime::now() + lockout, lockout)),
+            },
+        );
+
+        let env = wait_for(&dir.join("env"));
+        let var = |name: &str| {
+            env.lines()
+                .find_map(|line| line.strip_prefix(name)?.strip_prefix('='))
+                .map(str::to_string)
+        };
+        assert_eq!(var("RAYLOCK_EVENT").as_deref(), Some("lock"));
+        assert_eq!(var("RAYLOCK_FAILED_ATTEMPTS").as_deref(), Some("2"));
+        assert!(var("RAYLOCK_LOCKED_SECS").is_some());
+        let left: u64 = var("RAYLOCK_LOCKOUT_SECS").unwrap().parse().unwrap();
+        assert!((29..=31).contains(&left));
+    }
+}
@@ -0,0 +1,315 @@
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::sync::{Condvar, Mutex, MutexGuard, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::graph::AnimatedValue;
+use crate::sway_ipc::{self, IpcError, SwayIpc};
+
+static IDLE: OnceLock<Idle> = OnceLock::new();
+
+/// What happens to a locked screen nobody is typing at. Zero turns a step
+/// off, both are off unless configured.
+#[derive(Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IdleSettings {
+    pub dim_after_secs: u64,
+    // How dark dimming gets, 0 is untouched and 1 is black
+    pub dim_opacity: f32,
+    pub power_off_after_secs: u64,
+}
+
+impl Default for IdleSettings {
+    fn default() -> Self {
+        IdleSettings {
+            dim_after_secs: 0,
+            dim_opacity: 0.6,
+            power_off_after_secs: 0,
+        }
+    }
+}
+
+struct Idle {
+    dim_after: Option<Duration>,
+    dim_opacity: f32,
+    power_off_after: Option<Duration>,
+    sway_socket: Option<PathBuf>,
+    timeline: Mutex<Timeline>,
+    input: Condvar,
+}
+
+struct Timeline {
+    last_input: Instant,
+    dimmed: bool,
+    fade: AnimatedValue,
+    powered_off: bool,
+    want_off: bool,
+    // Set by `restore`, the outputs stay on from then on
+    stopped: bool,
+}
+
+/// Outputs are turned off and on from a thread of their own, sway can take
+/// its time answering
+pub fn start(settings: IdleSettings) {
+    let after = |secs| (secs > 0).then(|| Duration::from_secs(secs));
+    let idle = IDLE.get_or_init(|| {
+        Idle::new(
+            after(settings.dim_after_secs),
+            settings.dim_opacity,
+            after(settings.power_off_after_secs),
+            sway_ipc::socket_path().ok(),
+        )
+    });
+    thread::spawn(move || power_loop(idle));
+}
+
+fn power_loop(idle: &Idle) {
+    let mut timeline = idle.timeline.lock().unwrap();
+    loop {
+        if timeline.stopped {
+            return;
+        }
+        timeline = idle.advance(timeline);
+        if timeline.want_off != timeline.powered_off {
+            let off = timeline.want_off;
+            drop(timeline);
+            set_power(idle.sway_socket.as_deref(), off);
+            timeline = idle.timeline.lock().unwrap();
+            timeline.powered_off = off;
+            // `restore` may have run while sway was answering
+            if timeline.stopped && off {
+                set_power(idle.sway_socket.as_deref(), false);
+                timeline.powered_off = false;
+            }
+            continue;
+        }
+        timeline = match idle.next_step(&timeline) {
+            Some(wait) => idle.input.wait_timeout(timeline, wait).unwrap().0,
+            None => idle.input.wait(timeline).unwrap(),
+        };
+    }
+}
+
+impl Idle {
+    fn new(
+        dim_after: Option<Duration>,
+        dim_opacity: f32,
+        power_off_after: Option<Duration>,
+        sway_socket: Option<PathBuf>,
+    ) -> Self {
+        Idle {
+            dim_after,
+            dim_opacity,
+            power_off_after,
+            sway_socket,
+            timeline: Mutex::new(Timeline {
+                last_input: Instant::now(),
+                dimmed: false,
+                fade: AnimatedValue::new(0.),
+                powered_off: false,
+                want_off: false,
+                stopped: false,
+            }),
+            input: Condvar::new(),
+        }
+    }
+
+    fn advance<'a>(&self, mut timeline: MutexGuard<'a, Timeline>) -> MutexGuard<'a, Timeline> {
+        let idle_for = timeline.last_input.elapsed();
+        let reached = |after: Option<Duration>| after.is_some_and(|after| idle_for >= after);
+
+        let dimmed = reached(self.dim_after);
+        if dimmed != timeline.dimmed {
+            timeline.dimmed = dimmed;
+            let target = if dimmed { self.dim_opacity } else { 0. };
+            timeline.fade.update(target.clamp(0., 1.));
+        }
+        timeline.want_off = reached(self.power_off_after) && !timeline.stopped;
+        timeline
+    }
+
+    fn next_step(&self, timeline: &Timeline) -> Option<Duration> {
+        let idle_for = timeline.last_input.elapsed();
+        [self.dim_after, self.power_off_after]
+            .into_iter()
+            .flatten()
+            .filter(|after| *after > idle_for)
+            .map(|after| after - idle_for)
+            .min()
+    }
+
+    fn activity(&self) {
+        let mut timeline = self.timeline.lock().unwrap();
+        timeline.last_input = Instant::now();
+        drop(self.advance(timeline));
+        self.input.notify_all();
+    }
+
+    fn powered_off(&self) -> bool {
+        let timeline = self.timeline.lock().unwrap();
+        // Input flips `want_off` before the outputs are back on
+        timeline.powered_off && timeline.want_off
+    }
+
+    fn restore(&self) {
+        // A panic on the power thread may have poisoned it
+        let powered_off = match self.timeline.lock() {
+            Ok(mut timeline) => {
+                timeline.stopped = true;
+                timeline.want_off = false;
+                timeline.powered_off
+            }
+            Err(_) => true,
+        };
+        self.input.notify_all();
+        if powered_off {
+            set_power(self.sway_socket.as_deref(), false);
+        }
+    }
+}
+
+fn set_power(sway_socket: Option<&Path>, off: bool) {
+    let command = if off {
+        "output * power off"
+    } else {
+        "output * power on"
+    };
+    let result = sway_socket
+        .ok_or(IpcError::NoSocket)
+        .and_then(SwayIpc::connect_to)
+        .and_then(|mut ipc| ipc.run_command(command));
+    if let Err(e) = result {
+        eprintln!("raylock: could not run '{}': {}", command, e);
+    }
+}
+
+pub fn activity() {
+    if let Some(idle) = IDLE.get() {
+        idle.activity();
+    }
+}
+
+/// Starts the fade itself when the frame scheduled by `until_dim` gets here
+/// first
+pub fn dim_level() -> f32 {
+    IDLE.get().map_or(0., |idle| {
+        let mut timeline = idle.advance(idle.timeline.lock().unwrap());
+        timeline.fade.get_current_value()
+    })
+}
+
+pub fn fading() -> bool {
+    IDLE.get()
+        .is_some_and(|idle| idle.timeline.lock().unwrap().fade.animating())
+}
+
+pub fn powered_off() -> bool {
+    IDLE.get().is_some_and(Idle::powered_off)
+}
+
+pub fn until_dim() -> Option<Duration> {
+    let idle = IDLE.get()?;
+    let timeline = idle.timeline.lock().unwrap();
+    idle.dim_after?.checked_sub(timeline.last_input.elapsed())
+}
+
+/// Called on every exit, the power thread doesn't get to finish
+pub fn restore() {
+    if let Some(idle) = IDLE.get() {
+        idle.restore();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sway_ipc::tests::{frame, read_request};
+    use crate::sway_ipc::RUN_COMMAND;
+    use std::io::Write;
+    use std::os::unix::net::UnixListener;
+    use std::sync::mpsc::{self, Receiver};
+
+    const STEP: Duration = Duration::from_millis(100);
+    const PATIENCE: Duration = Duration::from_secs(2);
+
+    /// A sway that takes any number of connections and hands back each
+    /// command it was sent, with when it arrived
+    fn recording_sway(name: &str) -> (PathBuf, Receiver<(Instant, String)>) {
+        let path =
+            std::env::temp_dir().join(format!("raylock-idle-{}-{}.sock", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        let (sender, commands) = mpsc::channel();
+        thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let (kind, command) = read_request(&mut stream);
+                assert_eq!(kind, RUN_COMMAND);
+                let _ = sender.send((Instant::now(), command));
+                let _ = stream.write_all(&frame(RUN_COMMAND, br#"[{"success": true}]"#));
+            }
+        });
+        (path, commands)
+    }
+
+    fn start(dim_after: Duration, power_off_after: Duration, socket: &Path) -> &'static Idle {
+        let idle = Box::leak(Box::new(Idle::new(
+            Some(dim_after),
+            0.5,
+            Some(power_off_after),
+            Some(socket.to_path_buf()),
+        )));
+        thread::spawn(|| power_loop(idle));
+        idle
+    }
+
+    #[test]
+    fn dims_then_powers_off_and_wakes_on_input() {
+        let (path, commands) = recording_sway("timeline");
+        let started = Instant::now();
+        let idle = start(STEP, 3 * STEP, &path);
+
+        let (at, command) = commands.recv_timeout(PATIENCE).unwrap();
+        assert_eq!(command, "output * power off");
+        assert!(at >= started + 3 * STEP);
+        assert!(idle.timeline.lock().unwrap().dimmed);
+        // Recorded once sway has the command, the reply is still on its way
+        let deadline = Instant::now() + PATIENCE;
+        while !idle.powered_off() {
+            assert!(Instant::now() < deadline, "never powered off");
+            thread::sleep(STEP / 10);
+        }
+
+        idle.activity();
+        assert!(!idle.timeline.lock().unwrap().dimmed);
+        assert!(!idle.powered_off());
+        let (_, command) = commands.recv_timeout(PATIENCE).unwrap();
+        assert_eq!(command, "output * power on");
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn restore_powers_on_and_stops_the_timeline() {
+        let (path, commands) = recording_sway("restore");
+        let idle = start(STEP, STEP, &path);
+        let (_, command) = commands.recv_timeout(PATIENCE).unwrap();
+        assert_eq!(command, "output * power off");
+
+        idle.restore();
+        let (_, command) = commands.recv_timeout(PATIENCE).unwrap();
+        assert_eq!(command, "output * power on");
+        // Input after the lock ended doesn't start it over
+        idle.activity();
+        assert!(commands.recv_timeout(3 * STEP).is_err());
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn restore_leaves_powered_outputs_alone() {
+        let (path, commands) = recording_sway("untouched");
+        let idle = start(STEP, 10 * STEP, &path);
+        idle.restore();
+        assert!(commands.recv_timeout(3 * STEP).is_err());
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,39 @@
+use std::fs;
+use std::path::Path;
+
+const LEDS_DIR: &str = "/sys/class/leds";
+
+/// Lock keys and layout shown under the password ring
+#[derive(Clone, Default, PartialEq)]
+pub struct KeyboardStatus {
+    pub caps_lock: bool,
+    pub num_lock: bool,
+    // Name of the active xkb layout, like "English (US)"
+    pub layout: Option<String>,
+}
+
+/// Caps Lock and Num Lock from the keyboard LEDs, for the window mode where
+/// raylock never sees the xkb state. Any keyboard with the LED lit counts.
+pub fn read_leds() -> (bool, bool) {
+    let Ok(entries) = fs::read_dir(LEDS_DIR) else {
+        return (false, false);
+    };
+    let (mut caps_lock, mut num_lock) = (false, false);
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name.ends_with("::capslock") {
+            caps_lock |= led_on(&entry.path());
+        } else if name.ends_with("::numlock") {
+            num_lock |= led_on(&entry.path());
+        }
+    }
+    (caps_lock, num_lock)
+}
+
+fn led_on(led: &Path) -> bool {
+    fs::read_to_string(led.join("brightness"))
+        .ok()
+        .and_then(|brightness| brightness.trim().parse::<u32>().ok())
+        .is_some_and(|brightness| brightness > 0)
+}
@@ -1,19 +1,15 @@
-use battery::units::electric_potential::volt;
 use battery::units::energy::joule;
 use battery::units::power::watt;
 use battery::units::time::minute;
-use battery::units::time::second;
 use battery::Manager;
 use chrono::Local;
 use egui::Align2;
-use egui::Galley;
 use egui::Pos2;
 use egui::Rect;
-use std::env;
 use std::time::Instant;
 
-use crate::configs::*;
 use crate::graph::*;
+use crate::theme::Theme;
 
 pub struct InfoPane {
     manager: Manager,
@@ -51,27 +47,27 @@ impl InfoPane {
         // let mut rate = 0.;
         // let mut min_time;
 
-        for (idx, unsafe_battery) in self.manager.batteries()?.enumerate() {
+        if let Some(unsafe_battery) = self.manager.batteries()?.next() {
             let battery = unsafe_battery.unwrap();
             let percent = battery.energy().get::<joule>() / battery.energy_full().get::<joule>();
             let rate = battery.energy_rate().get::<watt>();
 
             let time_to_empty = battery.time_to_empty();
 
-            let time: f32 = if !time_to_empty.is_none() {
-                time_to_empty.unwrap().get::<minute>()
-            } else if !battery.time_to_full().is_none() {
-                battery.time_to_full().unwrap().get::<minute>()
+            let time: f32 = if let Some(time_to_empty) = time_to_empty {
+                time_to_empty.get::<minute>()
+            } else if let Some(time_to_full) = battery.time_to_full() {
+                time_to_full.get::<minute>()
             } else {
                 0.
             };
 
-            return Ok((BatteryState {
+            return Ok(BatteryState {
                 percent: percent * 100.0,
-                rate: rate,
-                time: time,
-                discharging: !time_to_empty.is_none(),
-            }));
+                rate,
+                time,
+                discharging: time_to_empty.is_some(),
+            });
 
             // percent *= battery
             //     .unwrap()
@@ -79,12 +75,12 @@ impl InfoPane {
             //     .get::<battery::units::ratio::percent>();
         }
 
-        Ok((BatteryState {
+        Ok(BatteryState {
             percent: 0.,
             rate: 0.,
             time: 0.,
             discharging: false,
-        }))
+        })
     }
 
     pub fn update(&mut self) {
@@ -97,7 +93,7 @@ impl InfoPane {
         self.last_update = Instant::now();
     }
 
-    pub fn render(&mut self, painter: &egui::Painter, rect: Rect) {
+    pub fn render(&mut self, painter: &egui::Painter, rect: Rect, theme: &Theme) {
         let now = Local::now();
 
         let mut y = 0.;
@@ -108,12 +104,12 @@ impl InfoPane {
                 y: rect.min.y,
             },
             Align2::LEFT_TOP,
-            format!("TIME: {}", now.format("UTC%Z %H:%M:%S%.f (%Y-%d-%m)")),
-            TEXT_FONT,
-            TEXT_COLOR,
+            format!("TIME: {}", now.format("UTC%Z %H:%M:%S (%Y-%d-%m)")),
+            theme.text_font(),
+            theme.text_color,
         );
 
-        y += TEXT_FONT.size;
+        y += theme.text_font_size;
 
         // let galley = painter.layout_no_wrap(format!("{}%", self.percent), TITLE_FONT, TEXT_COLOR);
         //
@@ -137,11 +133,11 @@ impl InfoPane {
                 self.state.time,
                 self.state.rate,
             ),
-            TEXT_FONT,
-            TEXT_COLOR,
+            theme.text_font(),
+            theme.text_color,
         );
 
-        y += TEXT_FONT.size;
+        y += theme.text_font_size;
 
         painter.text(
             Pos2 {
@@ -150,8 +146,8 @@ impl InfoPane {
             },
             Align2::LEFT_TOP,
             format!("OS: {}", self.unamea,),
-            TEXT_FONT,
-            TEXT_COLOR,
+            theme.text_font(),
+            theme.text_color,
         );
     }
 }
@@ -1,261 +1,123 @@
-use egui::Key;
-use std::fs::File;
-use std::path::Path;
-use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
 use std::thread;
 
-const LOCK_FILEPATH: &str = "/tmp/.raylock.lock";
+use crate::sway_ipc::{self, EventKind, EventStream, IpcError, SwayIpc};
 
-pub fn sway_lock_input() {
-    thread::spawn(move || {
-        let _ = Command::new("swaymsg").args(["mode", "lock"]).spawn();
-        // let _ = Command::new("swaymsg")
-        //     .args(["input", "type:touchpad", "events", "disabled"])
-        //     .spawn();
-    });
-}
+const LOCK_MODE: &str = "lock";
+const DEFAULT_MODE: &str = "default";
+const WINDOW_CRITERIA: &str = "[title=\"^raylock$\"]";
 
-pub fn sway_unlock_input() {
-    thread::spawn(move || {
-        let _ = Command::new("swaymsg").args(["mode", "default"]).spawn();
-        // let _ = Command::new("swaymsg")
-        //     .args(["input", "type:touchpad", "events", "enabled"])
-        //     .spawn();
-    });
-}
+// Cleared before leaving the lock mode so the watcher doesn't put it back
+static HOLDING_INPUT: AtomicBool = AtomicBool::new(false);
+// Keyboard layout as sway last reported it
+static LAYOUT: Mutex<Option<String>> = Mutex::new(None);
+// The output the window covers, and the ones plugged in since that we
+// turned off until unlock
+static OUTPUTS: Mutex<(Option<String>, Vec<String>)> = Mutex::new((None, Vec::new()));
 
-pub fn create_lock() {
-    let _ = File::create(LOCK_FILEPATH).unwrap();
-}
+/// Puts sway into the `lock` binding mode and keeps it there until
+/// `sway_unlock_input`. Fails if sway didn't switch, or with
+/// `TooManyOutputs` if there is more than one output for the window to cover.
+pub fn sway_lock_input() -> Result<(), IpcError> {
+    let mut ipc = SwayIpc::connect()?;
+    let outputs = ipc.active_outputs()?;
+    if outputs.len() > 1 {
+        return Err(IpcError::TooManyOutputs(outputs));
+    }
+    ipc.set_mode(LOCK_MODE)?;
+    *OUTPUTS.lock().unwrap() = (outputs.into_iter().next(), Vec::new());
+    HOLDING_INPUT.store(true, Ordering::SeqCst);
+    // Only feeds the layout indicator, not worth failing over
+    *LAYOUT.lock().unwrap() = ipc.keyboard_layout().unwrap_or(None);
 
-pub fn is_locked() -> bool {
-    Path::exists(Path::new(LOCK_FILEPATH))
+    let events = SwayIpc::connect()?.subscribe(&["mode", "output", "input"])?;
+    thread::spawn(move || watch_sway(events));
+    Ok(())
 }
 
-pub fn remove_lock() {
-    if !is_locked() {
+/// Leaves the lock mode and turns outputs plugged in while locked back on,
+/// does nothing if input isn't held
+pub fn sway_unlock_input() {
+    if !HOLDING_INPUT.swap(false, Ordering::SeqCst) {
         return;
     }
+    if let Err(e) = SwayIpc::connect().and_then(|mut ipc| ipc.set_mode(DEFAULT_MODE)) {
+        eprintln!("raylock: could not leave the lock mode: {}", e);
+    }
+    let disabled = std::mem::take(&mut OUTPUTS.lock().unwrap().1);
+    for name in disabled {
+        let command = format!("output \"{}\" enable", name);
+        if let Err(e) = SwayIpc::connect().and_then(|mut ipc| ipc.run_command(&command)) {
+            eprintln!("raylock: could not turn {} back on: {}", name, e);
+        }
+    }
+}
 
-    std::fs::remove_file(Path::new(LOCK_FILEPATH));
+/// The keyboard layout sway reports, kept current while input is held
+pub fn sway_layout() -> Option<String> {
+    LAYOUT.lock().unwrap().clone()
 }
 
-pub fn format_key(key: Key, shift_pressed: bool) -> String {
-    match key {
-        // Letters
-        Key::A
-        | Key::B
-        | Key::C
-        | Key::D
-        | Key::E
-        | Key::F
-        | Key::G
-        | Key::H
-        | Key::I
-        | Key::J
-        | Key::K
-        | Key::L
-        | Key::M
-        | Key::N
-        | Key::O
-        | Key::P
-        | Key::Q
-        | Key::R
-        | Key::S
-        | Key::T
-        | Key::U
-        | Key::V
-        | Key::W
-        | Key::X
-        | Key::Y
-        | Key::Z => {
-            let base_char = (key as u8 - Key::A as u8 + b'a') as char;
-            if shift_pressed {
-                base_char.to_uppercase().to_string()
-            } else {
-                base_char.to_string()
-            }
+// Puts the mode back if something else changes it, refullscreens the
+// window when outputs come and go, turns off new ones and follows layout
+// switches
+fn watch_sway(mut events: EventStream) {
+    loop {
+        let event = match events.next_event() {
+            Ok(event) => event,
+            Err(e) => {
+                eprintln!("raylock: lost the sway event stream: {}", e);
+                return;
+            }
+        };
+        if !HOLDING_INPUT.load(Ordering::SeqCst) {
+            return;
         }
 
-        // Numbers and their shift symbols
-        Key::Num0 => {
-            if shift_pressed {
-                ")".to_string()
-            } else {
-                "0".to_string()
+        let command = match event.kind {
+            EventKind::Mode if event.payload["change"] != LOCK_MODE => {
+                format!("mode \"{}\"", LOCK_MODE)
             }
-        }
-        Key::Num1 => {
-            if shift_pressed {
-                "!".to_string()
-            } else {
-                "1".to_string()
-            }
-        }
-        Key::Num2 => {
-            if shift_pressed {
-                "@".to_string()
-            } else {
-                "2".to_string()
+            EventKind::Output => format!("{} fullscreen enable, focus", WINDOW_CRITERIA),
+            EventKind::Input => {
+                if let Some(layout) = sway_ipc::layout_name(&event.payload["input"]) {
+                    *LAYOUT.lock().unwrap() = Some(layout);
+                }
+                continue;
             }
+            _ => continue,
+        };
+        if let Err(e) = SwayIpc::connect().and_then(|mut ipc| ipc.run_command(&command)) {
+            eprintln!("raylock: {}", e);
         }
-        Key::Num3 => {
-            if shift_pressed {
-                "#".to_string()
-            } else {
-                "3".to_string()
+        if event.kind == EventKind::Output {
+            if let Err(e) = disable_new_outputs() {
+                eprintln!("raylock: {}", e);
             }
         }
-        Key::Num4 => {
-            if shift_pressed {
-                "$".to_string()
-            } else {
-                "4".to_string()
-            }
-        }
-        Key::Num5 => {
-            if shift_pressed {
-                "%".to_string()
-            } else {
-                "5".to_string()
-            }
-        }
-        Key::Num6 => {
-            if shift_pressed {
-                "^".to_string()
-            } else {
-                "6".to_string()
-            }
-        }
-        Key::Num7 => {
-            if shift_pressed {
-                "&".to_string()
-            } else {
-                "7".to_string()
-            }
-        }
-        Key::Num8 => {
-            if shift_pressed {
-                "*".to_string()
-            } else {
-                "8".to_string()
-            }
-        }
-        Key::Num9 => {
-            if shift_pressed {
-                "(".to_string()
-            } else {
-                "9".to_string()
-            }
-        }
-
-        // Special characters
-        Key::Space => " ".to_string(),
-        // Key::Tab => "Tab".to_string(),
-        // Key::Enter => "Enter".to_string(),
-        // Key::Backspace => "Backspace".to_string(),
-        // Key::Escape => "Esc".to_string(),
-        // Key::Delete => "Del".to_string(),
-
-        // Arrow keys
-        // Key::ArrowLeft => "←".to_string(),
-        // Key::ArrowRight => "→".to_string(),
-        // Key::ArrowUp => "↑".to_string(),
-        // Key::ArrowDown => "↓".to_string(),
+    }
+}
 
-        // Punctuation and symbols
-        Key::Minus => {
-            if shift_pressed {
-                "_".to_string()
-            } else {
-                "-".to_string()
-            }
-        }
-        Key::Equals => {
-            if shift_pressed {
-                "+".to_string()
-            } else {
-                "=".to_string()
-            }
-        }
-        Key::OpenBracket => {
-            if shift_pressed {
-                "{".to_string()
-            } else {
-                "[".to_string()
-            }
-        }
-        Key::CloseBracket => {
-            if shift_pressed {
-                "}".to_string()
-            } else {
-                "]".to_string()
-            }
-        }
-        Key::Backslash => {
-            if shift_pressed {
-                "|".to_string()
-            } else {
-                "\\".to_string()
-            }
-        }
-        Key::Semicolon => {
-            if shift_pressed {
-                ":".to_string()
-            } else {
-                ";".to_string()
-            }
-        }
-        Key::Quote => {
-            if shift_pressed {
-                "\"".to_string()
-            } else {
-                "'".to_string()
-            }
-        }
-        Key::Comma => {
-            if shift_pressed {
-                "<".to_string()
-            } else {
-                ",".to_string()
-            }
-        }
-        Key::Period => {
-            if shift_pressed {
-                ">".to_string()
-            } else {
-                ".".to_string()
-            }
-        }
-        Key::Slash => {
-            if shift_pressed {
-                "?".to_string()
-            } else {
-                "/".to_string()
-            }
+// The window can't follow a monitor plugged in while locked, so it is
+// turned off instead of showing the desktop
+fn disable_new_outputs() -> Result<(), IpcError> {
+    let mut ipc = SwayIpc::connect()?;
+    let active = ipc.active_outputs()?;
+    let mut outputs = OUTPUTS.lock().unwrap();
+    let (covered, disabled) = &mut *outputs;
+    // The window moves on when its output goes away
+    if !covered.as_ref().is_some_and(|name| active.contains(name)) {
+        *covered = active.first().cloned();
+    }
+    for name in active {
+        if Some(&name) == covered.as_ref() {
+            continue;
         }
-        Key::Backtick => {
-            if shift_pressed {
-                "~".to_string()
-            } else {
-                "`".to_string()
-            }
+        ipc.run_command(&format!("output \"{}\" disable", name))?;
+        if !disabled.contains(&name) {
+            disabled.push(name);
         }
-
-        // Function keys
-        // Key::F1..=Key::F12 => format!("F{}", (key as u8 - Key::F1 as u8 + 1)),
-
-        // Modifier keys
-        // Key::Control => "Ctrl".to_string(),
-        // Key::Alt => "Alt".to_string(),
-        // Key::Shift => "Shift".to_string(),
-        // Key::Insert => "Insert".to_string(),
-        // Key::Home => "Home".to_string(),
-        // Key::End => "End".to_string(),
-        // Key::PageUp => "PgUp".to_string(),
-        // Key::PageDown => "PgDn".to_string(),
-
-        // Catch any other keys
-        _ => "".to_string(),
     }
+    Ok(())
 }
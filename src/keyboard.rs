@@ -0,0 +1,714 @@
+use std::ffi::{CStr, CString};
+use std::os::fd::{AsRawFd, OwnedFd};
+use std::os::raw::c_char;
+use std::ptr;
+use std::time::{Duration, Instant};
+
+use egui::Key;
+use libloading::Library;
+use xkbcommon_dl::{
+    xkb_compose_compile_flags, xkb_compose_state, xkb_compose_state_flags, xkb_compose_status,
+    xkb_compose_table, xkb_context, xkb_context_flags, xkb_key_direction, xkb_keymap,
+    xkb_keymap_compile_flags, xkb_keymap_format, xkb_state, xkb_state_component,
+    xkbcommon_compose_option, xkbcommon_option, XkbCommon, XkbCommonCompose,
+};
+
+use crate::indicators::KeyboardStatus;
+
+// Wayland sends evdev scancodes, xkb keycodes are offset by 8
+const EVDEV_OFFSET: u32 = 8;
+// Room for what one key or compose sequence types, plus xkb's NUL
+const TEXT_LEN: usize = 64;
+
+// Until the compositor sends wl_keyboard.repeat_info, same as sway's default
+const DEFAULT_REPEAT_RATE: i32 = 25;
+const DEFAULT_REPEAT_DELAY: Duration = Duration::from_millis(600);
+
+// xkbcommon-dl doesn't bind xkb_keymap_layout_get_name, it's looked up in
+// the same library by hand
+const LIBXKBCOMMON: &str = "libxkbcommon.so.0";
+type LayoutGetNameFn = unsafe extern "C" fn(*mut xkb_keymap, u32) -> *const c_char;
+
+/// What a key turns into. Typed characters are handed over one at a time
+/// instead of as egui's `Text`, whose String would leave the password on
+/// the heap.
+#[derive(Debug, PartialEq)]
+pub enum Input {
+    Event(egui::Event),
+    Char(char),
+}
+
+/// Translates wl_keyboard events into egui events through libxkbcommon,
+/// so the compositor's keymap (and layout switching) is respected.
+pub struct Keyboard {
+    xkb: &'static XkbCommon,
+    context: *mut xkb_context,
+    keymap: *mut xkb_keymap,
+    state: *mut xkb_state,
+    pub modifiers: egui::Modifiers,
+    layout_get_name: Option<(Library, LayoutGetNameFn)>,
+    // Keys per second, 0 turns repeat off
+    repeat_rate: i32,
+    repeat_delay: Duration,
+    // The key being held and when it next repeats
+    repeating: Option<(u32, Instant)>,
+    // Dead keys and compose sequences, None without a table for the locale
+    compose: Option<Compose>,
+}
+
+// The xkb objects are only touched from the wayland event loop thread
+unsafe impl Send for Keyboard {}
+
+impl Keyboard {
+    pub fn new() -> Option<Self> {
+        let xkb = xkbcommon_option()?;
+        let context = unsafe { (xkb.xkb_context_new)(xkb_context_flags::XKB_CONTEXT_NO_FLAGS) };
+        if context.is_null() {
+            return None;
+        }
+        let compose = Compose::new(context, &locale());
+        Some(Keyboard {
+            xkb,
+            context,
+            keymap: ptr::null_mut(),
+            state: ptr::null_mut(),
+            modifiers: egui::Modifiers::NONE,
+            layout_get_name: load_layout_get_name(),
+            repeat_rate: DEFAULT_REPEAT_RATE,
+            repeat_delay: DEFAULT_REPEAT_DELAY,
+            repeating: None,
+            compose,
+        })
+    }
+
+    /// Loads the XKB_V1 keymap the compositor shares through `fd`.
+    pub fn set_keymap(&mut self, fd: OwnedFd, size: usize) {
+        let map = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                size,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                fd.as_raw_fd(),
+                0,
+            )
+        };
+        if map == libc::MAP_FAILED {
+            return;
+        }
+
+        let keymap = unsafe {
+            (self.xkb.xkb_keymap_new_from_string)(
+                self.context,
+                map as *const c_char,
+                xkb_keymap_format::XKB_KEYMAP_FORMAT_TEXT_V1,
+                xkb_keymap_compile_flags::XKB_KEYMAP_COMPILE_NO_FLAGS,
+            )
+        };
+        unsafe { libc::munmap(map, size) };
+        if keymap.is_null() {
+            return;
+        }
+
+        let state = unsafe { (self.xkb.xkb_state_new)(keymap) };
+        if state.is_null() {
+            unsafe { (self.xkb.xkb_keymap_unref)(keymap) };
+            return;
+        }
+
+        self.release_keymap();
+        self.keymap = keymap;
+        self.state = state;
+        if let Some(compose) = &self.compose {
+            compose.reset();
+        }
+    }
+
+    pub fn update_modifiers(&mut self, depressed: u32, latched: u32, locked: u32, group: u32) {
+        if self.state.is_null() {
+            return;
+        }
+        unsafe {
+            (self.xkb.xkb_state_update_mask)(self.state, depressed, latched, locked, 0, 0, group)
+        };
+        self.modifiers = egui::Modifiers {
+            alt: self.mod_active(xkbcommon_dl::XKB_MOD_NAME_ALT),
+            ctrl: self.mod_active(xkbcommon_dl::XKB_MOD_NAME_CTRL),
+            shift: self.mod_active(xkbcommon_dl::XKB_MOD_NAME_SHIFT),
+            mac_cmd: false,
+            command: self.mod_active(xkbcommon_dl::XKB_MOD_NAME_CTRL),
+        };
+    }
+
+    fn mod_active(&self, name: &[u8]) -> bool {
+        self.mod_in(name, xkb_state_component::XKB_STATE_MODS_EFFECTIVE)
+    }
+
+    fn mod_in(&self, name: &[u8], component: xkb_state_component) -> bool {
+        unsafe {
+            (self.xkb.xkb_state_mod_name_is_active)(
+                self.state,
+                name.as_ptr() as *const c_char,
+                component,
+            ) > 0
+        }
+    }
+
+    /// Lock keys and layout as of the last modifiers event
+    pub fn status(&self) -> KeyboardStatus {
+        if self.state.is_null() {
+            return KeyboardStatus::default();
+        }
+        let locked = || xkb_state_component::XKB_STATE_MODS_LOCKED;
+        KeyboardStatus {
+            caps_lock: self.mod_in(xkbcommon_dl::XKB_MOD_NAME_CAPS, locked()),
+            num_lock: self.mod_in(xkbcommon_dl::XKB_MOD_NAME_NUM, locked()),
+            layout: self.layout_name(),
+        }
+    }
+
+    fn layout_name(&self) -> Option<String> {
+        let (_, get_name) = self.layout_get_name.as_ref()?;
+        let layout = unsafe {
+            (self.xkb.xkb_state_serialize_layout)(
+                self.state,
+                xkb_state_component::XKB_STATE_LAYOUT_EFFECTIVE,
+            )
+        };
+        let name = unsafe { get_name(self.keymap, layout) };
+        if name.is_null() {
+            return None;
+        }
+        Some(
+            unsafe { CStr::from_ptr(name) }
+                .to_string_lossy()
+                .into_owned(),
+        )
+    }
+
+    /// From wl_keyboard.repeat_info, `rate` in keys per second and `delay`
+    /// in milliseconds
+    pub fn set_repeat_info(&mut self, rate: i32, delay: i32) {
+        self.repeat_rate = rate;
+        self.repeat_delay = Duration::from_millis(delay.max(0) as u64);
+        if rate <= 0 {
+            self.repeating = None;
+        }
+    }
+
+    /// Stops repeating, for when the surface loses keyboard focus
+    pub fn stop_repeat(&mut self) {
+        self.repeating = None;
+    }
+
+    /// When the held key repeats next, the event loop wakes up for it
+    pub fn next_repeat(&self) -> Option<Instant> {
+        self.repeating.map(|(_, at)| at)
+    }
+
+    /// Input for the held key if it's due to repeat by `now`
+    pub fn repeat(&mut self, now: Instant, mut input: impl FnMut(Input)) {
+        let Some((scancode, at)) = self.repeating else {
+            return;
+        };
+        if now < at || self.state.is_null() {
+            return;
+        }
+        let interval = Duration::from_secs(1) / self.repeat_rate.max(1) as u32;
+        self.repeating = Some((scancode, now + interval));
+        self.key_events(scancode + EVDEV_OFFSET, true, true, &mut input);
+    }
+
+    /// Turns a key press or release into input: a `Key` event for keys
+    /// egui knows, followed by the characters of printable input.
+    pub fn key(&mut self, scancode: u32, pressed: bool, mut input: impl FnMut(Input)) {
+        if self.state.is_null() {
+            return;
+        }
+        let keycode = scancode + EVDEV_OFFSET;
+        self.key_events(keycode, pressed, false, &mut input);
+
+        // Only the last key pressed repeats, and only while it's held.
+        // Modifiers don't repeat and leave a held key repeating.
+        let repeats = unsafe { (self.xkb.xkb_keymap_key_repeats)(self.keymap, keycode) } > 0;
+        if pressed && repeats && self.repeat_rate > 0 {
+            self.repeating = Some((scancode, Instant::now() + self.repeat_delay));
+        } else if !pressed && self.repeating.is_some_and(|(held, _)| held == scancode) {
+            self.repeating = None;
+        }
+
+        let direction = if pressed {
+            xkb_key_direction::XKB_KEY_DOWN
+        } else {
+            xkb_key_direction::XKB_KEY_UP
+        };
+        unsafe { (self.xkb.xkb_state_update_key)(self.state, keycode, direction) };
+    }
+
+    fn key_events(
+        &mut self,
+        keycode: u32,
+        pressed: bool,
+        repeat: bool,
+        input: &mut impl FnMut(Input),
+    ) {
+        if let Some(key) = self.egui_key(keycode) {
+            input(Input::Event(egui::Event::Key {
+                key,
+                physical_key: None,
+                pressed,
+                repeat,
+                modifiers: self.modifiers,
+            }));
+        }
+
+        if pressed && !self.modifiers.ctrl && !self.modifiers.alt {
+            let mut buffer = [0; TEXT_LEN];
+            let text = self.composed_utf8(keycode, &mut buffer);
+            if !text.chars().any(char::is_control) {
+                text.chars().for_each(|ch| input(Input::Char(ch)));
+            }
+            unsafe { libc::explicit_bzero(buffer.as_mut_ptr().cast(), buffer.len()) };
+        }
+    }
+
+    // The text of the key after any dead key or compose sequence before it.
+    // Empty while a sequence is under way or when one is cancelled.
+    fn composed_utf8<'a>(&self, keycode: u32, buffer: &'a mut [u8; TEXT_LEN]) -> &'a str {
+        let Some(compose) = &self.compose else {
+            return self.utf8(keycode, buffer);
+        };
+        let keysym = unsafe { (self.xkb.xkb_state_key_get_one_sym)(self.state, keycode) };
+        match compose.feed(keysym) {
+            xkb_compose_status::XKB_COMPOSE_COMPOSING => "",
+            xkb_compose_status::XKB_COMPOSE_COMPOSED => {
+                let text = compose.utf8(buffer);
+                compose.reset();
+                text
+            }
+            xkb_compose_status::XKB_COMPOSE_CANCELLED => {
+                compose.reset();
+                ""
+            }
+            xkb_compose_status::XKB_COMPOSE_NOTHING => self.utf8(keycode, buffer),
+        }
+    }
+
+    fn utf8<'a>(&self, keycode: u32, buffer: &'a mut [u8; TEXT_LEN]) -> &'a str {
+        let len = unsafe {
+            (self.xkb.xkb_state_key_get_utf8)(
+                self.state,
+                keycode,
+                buffer.as_mut_ptr().cast(),
+                buffer.len(),
+            )
+        };
+        written(buffer, len)
+    }
+
+    // The unshifted symbol of the key in the active layout, like winit's
+    // "key without modifiers"
+    fn egui_key(&self, keycode: u32) -> Option<Key> {
+        let layout = unsafe { (self.xkb.xkb_state_key_get_layout)(self.state, keycode) };
+        let mut syms: *const u32 = ptr::null();
+        let count = unsafe {
+            (self.xkb.xkb_keymap_key_get_syms_by_level)(self.keymap, keycode, layout, 0, &mut syms)
+        };
+        if count < 1 || syms.is_null() {
+            return None;
+        }
+        let keysym = unsafe { *syms };
+
+        let mut name = [0 as c_char; 64];
+        let len = unsafe { (self.xkb.xkb_keysym_get_name)(keysym, name.as_mut_ptr(), name.len()) };
+        if len <= 0 {
+            return None;
+        }
+        let name = unsafe { CStr::from_ptr(name.as_ptr()) }.to_string_lossy();
+
+        match name.as_ref() {
+            "BackSpace" => Some(Key::Backspace),
+            "KP_Enter" => Some(Key::Enter),
+            "space" => Some(Key::Space),
+            _ => Key::from_name(&name).or_else(|| {
+                let c = char::from_u32(unsafe { (self.xkb.xkb_keysym_to_utf32)(keysym) })?;
+                Key::from_name(&c.to_string())
+            }),
+        }
+    }
+
+    fn release_keymap(&mut self) {
+        unsafe {
+            if !self.state.is_null() {
+                (self.xkb.xkb_state_unref)(self.state);
+            }
+            if !self.keymap.is_null() {
+                (self.xkb.xkb_keymap_unref)(self.keymap);
+            }
+        }
+        self.state = ptr::null_mut();
+        self.keymap = ptr::null_mut();
+    }
+}
+
+/// The compose table for the user's locale and the state of the sequence
+/// being typed
+struct Compose {
+    xkb: &'static XkbCommonCompose,
+    table: *mut xkb_compose_table,
+    state: *mut xkb_compose_state,
+}
+
+impl Compose {
+    fn new(context: *mut xkb_context, locale: &CStr) -> Option<Self> {
+        let xkb = xkbcommon_compose_option()?;
+        let table = unsafe {
+            (xkb.xkb_compose_table_new_from_locale)(
+                context,
+                locale.as_ptr(),
+                xkb_compose_compile_flags::XKB_COMPOSE_COMPILE_NO_FLAGS,
+            )
+        };
+        if table.is_null() {
+            return None;
+        }
+        let state = unsafe {
+            (xkb.xkb_compose_state_new)(table, xkb_compose_state_flags::XKB_COMPOSE_STATE_NO_FLAGS)
+        };
+        if state.is_null() {
+            unsafe { (xkb.xkb_compose_table_unref)(table) };
+            return None;
+        }
+        Some(Compose { xkb, table, state })
+    }
+
+    fn feed(&self, keysym: u32) -> xkb_compose_status {
+        unsafe {
+            (self.xkb.xkb_compose_state_feed)(self.state, keysym);
+            (self.xkb.xkb_compose_state_get_status)(self.state)
+        }
+    }
+
+    fn utf8<'a>(&self, buffer: &'a mut [u8; TEXT_LEN]) -> &'a str {
+        let len = unsafe {
+            (self.xkb.xkb_compose_state_get_utf8)(
+                self.state,
+                buffer.as_mut_ptr().cast(),
+                buffer.len(),
+            )
+        };
+        written(buffer, len)
+    }
+
+    fn reset(&self) {
+        unsafe { (self.xkb.xkb_compose_state_reset)(self.state) };
+    }
+}
+
+impl Drop for Compose {
+    fn drop(&mut self) {
+        unsafe {
+            (self.xkb.xkb_compose_state_unref)(self.state);
+            (self.xkb.xkb_compose_table_unref)(self.table);
+        }
+    }
+}
+
+// The text xkb wrote to `buffer`, `len` is the length it needed. Nothing
+// if that didn't fit.
+fn written(buffer: &[u8; TEXT_LEN], len: i32) -> &str {
+    match usize::try_from(len) {
+        Ok(len) if len < buffer.len() => std::str::from_utf8(&buffer[..len]).unwrap_or(""),
+        _ => "",
+    }
+}
+
+// Same lookup as setlocale(LC_CTYPE, ""), which picks the compose table
+fn locale() -> CString {
+    ["LC_ALL", "LC_CTYPE", "LANG"]
+        .into_iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .and_then(|value| CString::new(value).ok())
+        .unwrap_or_else(|| c"C".to_owned())
+}
+
+// Without it the layout indicator is left out, typing still works
+fn load_layout_get_name() -> Option<(Library, LayoutGetNameFn)> {
+    unsafe {
+        let lib = Library::new(LIBXKBCOMMON).ok()?;
+        let get_name = *lib
+            .get::<LayoutGetNameFn>(b"xkb_keymap_layout_get_name\0")
+            .ok()?;
+        Some((lib, get_name))
+    }
+}
+
+impl Drop for Keyboard {
+    fn drop(&mut self) {
+        self.release_keymap();
+        unsafe { (self.xkb.xkb_context_unref)(self.context) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use xkbcommon_dl::xkb_rule_names;
+
+    // evdev scancodes, as wl_keyboard sends them
+    const KEY_1: u32 = 2;
+    const KEY_EQUAL: u32 = 13;
+    const KEY_BACKSPACE: u32 = 14;
+    const KEY_Q: u32 = 16;
+    const KEY_E: u32 = 18;
+    const KEY_R: u32 = 19;
+    const KEY_Y: u32 = 21;
+    const KEY_I: u32 = 23;
+    const KEY_LEFTBRACE: u32 = 26;
+    const KEY_A: u32 = 30;
+    const KEY_H: u32 = 35;
+    const KEY_APOSTROPHE: u32 = 40;
+    const KEY_GRAVE: u32 = 41;
+    const KEY_LEFTSHIFT: u32 = 42;
+    const KEY_Z: u32 = 44;
+    const KEY_C: u32 = 46;
+    const KEY_SPACE: u32 = 57;
+
+    // Modifier masks as the compositor sends them for the keymaps above
+    const NONE: u32 = 0;
+    const SHIFT: u32 = 1;
+    const ALTGR: u32 = 1 << 7;
+
+    /// A keyboard given `layout` the way a compositor does it, as keymap
+    /// text in a shared file. `None` without libxkbcommon or its data.
+    pub fn keyboard(layout: &str, variant: &str) -> Option<Keyboard> {
+        let mut keyboard = Keyboard::new()?;
+        let layout = CString::new(layout).unwrap();
+        let variant = CString::new(variant).unwrap();
+        let names = xkb_rule_names {
+            rules: ptr::null(),
+            model: c"pc105".as_ptr(),
+            layout: layout.as_ptr(),
+            variant: variant.as_ptr(),
+            options: ptr::null(),
+        };
+        let text = unsafe {
+            let keymap = (keyboard.xkb.xkb_keymap_new_from_names)(
+                keyboard.context,
+                &names,
+                xkb_keymap_compile_flags::XKB_KEYMAP_COMPILE_NO_FLAGS,
+            );
+            if keymap.is_null() {
+                return None;
+            }
+            let text = (keyboard.xkb.xkb_keymap_get_as_string)(
+                keymap,
+                xkb_keymap_format::XKB_KEYMAP_FORMAT_TEXT_V1,
+            );
+            (keyboard.xkb.xkb_keymap_unref)(keymap);
+            let copy = CStr::from_ptr(text).to_bytes_with_nul().to_vec();
+            libc::free(text as *mut libc::c_void);
+            copy
+        };
+
+        let fd = unsafe { libc::memfd_create(c"keymap".as_ptr(), libc::MFD_CLOEXEC) };
+        assert!(fd >= 0);
+        let mut file =
+            std::fs::File::from(unsafe { <OwnedFd as std::os::fd::FromRawFd>::from_raw_fd(fd) });
+        file.write_all(&text).unwrap();
+        keyboard.set_keymap(file.into(), text.len());
+        assert!(!keyboard.state.is_null());
+        // The tests shouldn't depend on the locale they run in
+        keyboard.compose = Compose::new(keyboard.context, c"en_US.UTF-8");
+        Some(keyboard)
+    }
+
+    /// Replays wl_keyboard modifiers, key and key release events, returns
+    /// the text typed
+    fn type_keys(keyboard: &mut Keyboard, keys: &[(u32, u32)]) -> String {
+        let mut typed = String::new();
+        for &(scancode, modifiers) in keys {
+            keyboard.update_modifiers(modifiers, 0, 0, 0);
+            keyboard.key(scancode, true, |input| {
+                if let Input::Char(ch) = input {
+                    typed.push(ch);
+                }
+            });
+            keyboard.key(scancode, false, |_| {});
+        }
+        keyboard.update_modifiers(0, 0, 0, 0);
+        typed
+    }
+
+    #[test]
+    fn types_through_the_us_layout() {
+        let Some(mut keyboard) = keyboard("us", "") else {
+            eprintln!("skipping: no libxkbcommon");
+            return;
+        };
+        let keys = [
+            (KEY_Z, NONE),
+            (KEY_A, SHIFT),
+            (KEY_1, SHIFT),
+            (KEY_Y, NONE),
+            (KEY_SPACE, NONE),
+            (KEY_1, NONE),
+        ];
+        assert_eq!(type_keys(&mut keyboard, &keys), "zA!y 1");
+    }
+
+    #[test]
+    fn types_through_the_de_layout() {
+        let Some(mut keyboard) = keyboard("de", "") else {
+            eprintln!("skipping: no libxkbcommon");
+            return;
+        };
+        // Z and Y swap places, [ is ü, AltGr+Q is @
+        let keys = [
+            (KEY_Y, SHIFT),
+            (KEY_LEFTBRACE, NONE),
+            (KEY_R, NONE),
+            (KEY_I, NONE),
+            (KEY_C, NONE),
+            (KEY_H, NONE),
+            (KEY_Q, ALTGR),
+            (KEY_1, NONE),
+        ];
+        assert_eq!(type_keys(&mut keyboard, &keys), "Zürich@1");
+    }
+
+    #[test]
+    fn dead_keys_combine_with_the_next_key() {
+        let Some(mut dead) = keyboard("de", "") else {
+            eprintln!("skipping: no libxkbcommon");
+            return;
+        };
+        if dead.compose.is_none() {
+            eprintln!("skipping: no compose table");
+            return;
+        }
+        // ^ then e, ´ then Shift+E, ^ then space for the accent itself
+        let keys = [
+            (KEY_GRAVE, NONE),
+            (KEY_E, NONE),
+            (KEY_EQUAL, NONE),
+            (KEY_E, SHIFT),
+            (KEY_GRAVE, NONE),
+            (KEY_SPACE, NONE),
+        ];
+        assert_eq!(type_keys(&mut dead, &keys), "êÉ^");
+
+        let Some(mut plain) = keyboard("de", "nodeadkeys") else {
+            return;
+        };
+        assert_eq!(
+            type_keys(&mut plain, &[(KEY_GRAVE, NONE), (KEY_E, NONE)]),
+            "^e"
+        );
+    }
+
+    #[test]
+    fn the_us_intl_apostrophe_is_a_dead_acute() {
+        let Some(mut keyboard) = keyboard("us", "intl") else {
+            eprintln!("skipping: no libxkbcommon");
+            return;
+        };
+        if keyboard.compose.is_none() {
+            eprintln!("skipping: no compose table");
+            return;
+        }
+        let keys = [
+            (KEY_C, NONE),
+            (KEY_A, NONE),
+            (KEY_APOSTROPHE, NONE),
+            (KEY_E, NONE),
+            // Cancelled by a key it doesn't combine with, nothing is typed
+            (KEY_APOSTROPHE, NONE),
+            (KEY_Q, NONE),
+            (KEY_APOSTROPHE, NONE),
+            (KEY_SPACE, NONE),
+        ];
+        assert_eq!(type_keys(&mut keyboard, &keys), "caé'");
+    }
+
+    fn repeated(keyboard: &mut Keyboard, now: Instant) -> Vec<Input> {
+        let mut repeated = Vec::new();
+        keyboard.repeat(now, |input| repeated.push(input));
+        repeated
+    }
+
+    fn is_backspace(input: &Input) -> bool {
+        matches!(
+            input,
+            Input::Event(egui::Event::Key {
+                key: Key::Backspace,
+                pressed: true,
+                repeat: true,
+                ..
+            })
+        )
+    }
+
+    #[test]
+    fn held_keys_repeat_after_the_delay() {
+        let Some(mut keyboard) = keyboard("us", "") else {
+            eprintln!("skipping: no libxkbcommon");
+            return;
+        };
+        keyboard.set_repeat_info(10, 200);
+        let pressed_at = Instant::now();
+        keyboard.key(KEY_BACKSPACE, true, |_| {});
+        let first = keyboard.next_repeat().unwrap();
+        assert!(first >= pressed_at + Duration::from_millis(200));
+
+        assert!(repeated(&mut keyboard, pressed_at).is_empty());
+        let events = repeated(&mut keyboard, first);
+        assert!(events.len() == 1 && is_backspace(&events[0]));
+        // Then at the rate, 10 a second
+        assert_eq!(
+            keyboard.next_repeat(),
+            Some(first + Duration::from_millis(100))
+        );
+
+        keyboard.key(KEY_BACKSPACE, false, |_| {});
+        assert_eq!(keyboard.next_repeat(), None);
+        assert!(repeated(&mut keyboard, first + Duration::from_secs(1)).is_empty());
+    }
+
+    #[test]
+    fn repeats_text_with_the_modifiers_held() {
+        let Some(mut keyboard) = keyboard("us", "") else {
+            eprintln!("skipping: no libxkbcommon");
+            return;
+        };
+        keyboard.key(KEY_A, true, |_| {});
+        // Shift pressed while A is held doesn't stop it
+        keyboard.key(KEY_LEFTSHIFT, true, |_| {});
+        keyboard.update_modifiers(1, 0, 0, 0);
+        let at = keyboard.next_repeat().unwrap();
+        let text: Vec<_> = repeated(&mut keyboard, at)
+            .into_iter()
+            .filter_map(|input| match input {
+                Input::Char(ch) => Some(ch),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(text, ['A']);
+    }
+
+    #[test]
+    fn modifiers_and_a_zero_rate_dont_repeat() {
+        let Some(mut keyboard) = keyboard("us", "") else {
+            eprintln!("skipping: no libxkbcommon");
+            return;
+        };
+        keyboard.key(KEY_LEFTSHIFT, true, |_| {});
+        assert_eq!(keyboard.next_repeat(), None);
+
+        keyboard.set_repeat_info(0, 200);
+        keyboard.key(KEY_A, true, |_| {});
+        assert_eq!(keyboard.next_repeat(), None);
+    }
+}
@@ -0,0 +1,167 @@
+use egui::{Key, Modifiers};
+use serde::{Deserialize, Deserializer};
+use std::str::FromStr;
+
+/// Editing commands for the password field
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Submit,
+    DeleteChar,
+    DeleteWord,
+    Clear,
+    PowerMenu,
+}
+
+/// A key plus the modifiers that have to be held, written like "Ctrl+U"
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct KeyCombo {
+    key: Key,
+    ctrl: bool,
+    alt: bool,
+    shift: bool,
+}
+
+impl KeyCombo {
+    fn matches(&self, key: Key, modifiers: Modifiers) -> bool {
+        self.key == key
+            && self.ctrl == modifiers.ctrl
+            && self.alt == modifiers.alt
+            && self.shift == modifiers.shift
+    }
+}
+
+impl FromStr for KeyCombo {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts: Vec<&str> = s.split('+').map(str::trim).collect();
+        let name = parts.pop().unwrap_or_default();
+        let key = Key::from_name(name).ok_or_else(|| format!("unknown key '{}'", name))?;
+
+        let mut combo = KeyCombo {
+            key,
+            ctrl: false,
+            alt: false,
+            shift: false,
+        };
+        for modifier in parts {
+            match modifier.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => combo.ctrl = true,
+                "alt" => combo.alt = true,
+                "shift" => combo.shift = true,
+                _ => return Err(format!("unknown modifier '{}' in '{}'", modifier, s)),
+            }
+        }
+        Ok(combo)
+    }
+}
+
+impl<'de> Deserialize<'de> for KeyCombo {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Which keys trigger which action. Setting an action in the config
+/// replaces its default keys.
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeyBindings {
+    pub submit: Vec<KeyCombo>,
+    pub delete_char: Vec<KeyCombo>,
+    pub delete_word: Vec<KeyCombo>,
+    pub clear: Vec<KeyCombo>,
+    pub power_menu: Vec<KeyCombo>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        let combos = |names: &[&str]| names.iter().map(|name| name.parse().unwrap()).collect();
+        KeyBindings {
+            submit: combos(&["Enter"]),
+            delete_char: combos(&["Backspace"]),
+            delete_word: combos(&["Ctrl+W", "Ctrl+Backspace"]),
+            clear: combos(&["Escape", "Ctrl+U"]),
+            power_menu: combos(&["Ctrl+Alt+Delete"]),
+        }
+    }
+}
+
+impl KeyBindings {
+    pub fn action(&self, key: Key, modifiers: Modifiers) -> Option<Action> {
+        [
+            (&self.submit, Action::Submit),
+            (&self.delete_char, Action::DeleteChar),
+            (&self.delete_word, Action::DeleteWord),
+            (&self.clear, Action::Clear),
+            (&self.power_menu, Action::PowerMenu),
+        ]
+        .into_iter()
+        .find(|(combos, _)| combos.iter().any(|combo| combo.matches(key, modifiers)))
+        .map(|(_, action)| action)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CTRL: Modifiers = Modifiers::CTRL;
+
+    #[test]
+    fn combos_parse() {
+        let combo: KeyCombo = "Ctrl+Alt+Delete".parse().unwrap();
+        assert_eq!(
+            combo,
+            KeyCombo {
+                key: Key::Delete,
+                ctrl: true,
+                alt: true,
+                shift: false,
+            }
+        );
+        assert_eq!(
+            "control + shift + U".parse(),
+            "Ctrl+Shift+U".parse::<KeyCombo>()
+        );
+
+        assert_eq!(
+            "Ctrl+Nope".parse::<KeyCombo>(),
+            Err("unknown key 'Nope'".to_string())
+        );
+        assert_eq!(
+            "Super+U".parse::<KeyCombo>(),
+            Err("unknown modifier 'Super' in 'Super+U'".to_string())
+        );
+        assert!("".parse::<KeyCombo>().is_err());
+    }
+
+    #[test]
+    fn modifiers_match_exactly() {
+        let keys = KeyBindings::default();
+        assert_eq!(keys.action(Key::U, CTRL), Some(Action::Clear));
+        assert_eq!(keys.action(Key::U, CTRL | Modifiers::SHIFT), None);
+        assert_eq!(keys.action(Key::U, Modifiers::NONE), None);
+        assert_eq!(
+            keys.action(Key::Backspace, Modifiers::NONE),
+            Some(Action::DeleteChar)
+        );
+        assert_eq!(keys.action(Key::Backspace, CTRL), Some(Action::DeleteWord));
+    }
+
+    #[test]
+    fn configured_keys_replace_the_defaults() {
+        let keys: KeyBindings = toml::from_str(r#"clear = ["Ctrl+K"]"#).unwrap();
+        assert_eq!(keys.action(Key::K, CTRL), Some(Action::Clear));
+        assert_eq!(keys.action(Key::U, CTRL), None);
+        assert_eq!(keys.action(Key::Escape, Modifiers::NONE), None);
+        // Actions left out keep theirs
+        assert_eq!(
+            keys.action(Key::Enter, Modifiers::NONE),
+            Some(Action::Submit)
+        );
+
+        assert!(toml::from_str::<KeyBindings>(r#"clear = ["Ctrl+Nope"]"#).is_err());
+    }
+}
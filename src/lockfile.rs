@@ -0,0 +1,224 @@
+use std::env;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, Write};
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+const LOCK_FILENAME: &str = "raylock.lock";
+
+static HELD: Mutex<Option<File>> = Mutex::new(None);
+
+#[derive(Debug)]
+pub enum LockError {
+    AlreadyRunning { pid: Option<u32> },
+    Io(PathBuf, io::Error),
+}
+
+impl fmt::Display for LockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LockError::AlreadyRunning { pid: Some(pid) } => write!(
+                f,
+                "raylock is already running (pid {}), use --force to start anyway",
+                pid
+            ),
+            LockError::AlreadyRunning { pid: None } => {
+                write!(f, "raylock is already running, use --force to start anyway")
+            }
+            LockError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+        }
+    }
+}
+
+impl std::error::Error for LockError {}
+
+fn lock_path() -> PathBuf {
+    match env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir).join(LOCK_FILENAME),
+        None => env::temp_dir().join(format!("raylock-{}.lock", unsafe { libc::getuid() })),
+    }
+}
+
+/// Takes the single instance lock. A lock left behind by a process that
+/// died is reclaimed, `force` also takes over one that is still alive.
+pub fn acquire(force: bool) -> Result<(), LockError> {
+    let path = lock_path();
+    let io_error = |e| LockError::Io(path.clone(), e);
+
+    let (mut file, locked) = open_locked(&path).map_err(io_error)?;
+    if !locked {
+        // No record yet means another instance is between flock and write
+        let owner = read_owner(&mut file);
+        let alive = owner.is_none_or(|(pid, start)| start_time(pid) == Some(start));
+        if alive && !force {
+            return Err(LockError::AlreadyRunning {
+                pid: owner.map(|(pid, _)| pid),
+            });
+        }
+
+        // The flock is held by someone other than the recorded owner
+        fs::remove_file(&path).map_err(io_error)?;
+        let (replaced, locked) = open_locked(&path).map_err(io_error)?;
+        if !locked {
+            return Err(io_error(io::Error::from(io::ErrorKind::WouldBlock)));
+        }
+        file = replaced;
+    }
+
+    let pid = std::process::id();
+    let start = start_time(pid).unwrap_or(0);
+    file.set_len(0).map_err(io_error)?;
+    file.rewind().map_err(io_error)?;
+    writeln!(file, "{} {}", pid, start).map_err(io_error)?;
+
+    *HELD.lock().unwrap() = Some(file);
+    Ok(())
+}
+
+/// The file stays behind, unlinking it would let a newcomer lock a fresh
+/// file while someone still holds the old one
+pub fn release() {
+    // Called from the panic hook
+    let mut held = HELD.lock().unwrap_or_else(|e| e.into_inner());
+    held.take();
+}
+
+// A file replaced between our open and flock (by --force) is opened again,
+// a lock on an unlinked inode protects nothing
+fn open_locked(path: &Path) -> io::Result<(File, bool)> {
+    loop {
+        let file = open(path)?;
+        if !try_flock(&file) {
+            return Ok((file, false));
+        }
+        let on_disk = fs::symlink_metadata(path);
+        let held = file.metadata()?;
+        if on_disk.is_ok_and(|on_disk| on_disk.ino() == held.ino() && on_disk.dev() == held.dev()) {
+            return Ok((file, true));
+        }
+    }
+}
+
+fn open(path: &Path) -> io::Result<File> {
+    // O_NOFOLLOW so a symlink planted in a shared /tmp can't redirect us
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .mode(0o600)
+        .custom_flags(libc::O_NOFOLLOW | libc::O_CLOEXEC)
+        .open(path)?;
+
+    if file.metadata()?.uid() != unsafe { libc::getuid() } {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "lock file belongs to another user",
+        ));
+    }
+    Ok(file)
+}
+
+fn try_flock(file: &File) -> bool {
+    unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) == 0 }
+}
+
+fn read_owner(file: &mut File) -> Option<(u32, u64)> {
+    let mut contents = String::new();
+    file.read_to_string(&mut contents).ok()?;
+    let mut fields = contents.split_whitespace();
+    let pid = fields.next()?.parse().ok()?;
+    let start = fields.next()?.parse().ok()?;
+    Some((pid, start))
+}
+
+// Start time in clock ticks since boot, tells a live owner apart from a
+// reused PID
+fn start_time(pid: u32) -> Option<u64> {
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // The command name can contain spaces, fields are counted after it
+    let after_comm = &stat[stat.rfind(')')? + 1..];
+    after_comm.split_whitespace().nth(19)?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // HELD and XDG_RUNTIME_DIR are process wide
+    static ONE_AT_A_TIME: Mutex<()> = Mutex::new(());
+
+    // Runs `test` with XDG_RUNTIME_DIR pointing at a fresh directory and a
+    // flock on the lock file held by a record of `owner`, like another
+    // instance would leave it
+    fn with_owner(name: &str, owner: &str, test: impl FnOnce()) {
+        let _turn = ONE_AT_A_TIME.lock().unwrap_or_else(|e| e.into_inner());
+        let dir = env::temp_dir().join(format!("raylock-lock-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        env::set_var("XDG_RUNTIME_DIR", &dir);
+
+        let mut other = open(&lock_path()).unwrap();
+        assert!(try_flock(&other));
+        write!(other, "{}", owner).unwrap();
+
+        test();
+        release();
+        drop(other);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    fn recorded_pid() -> u32 {
+        let mut file = File::open(lock_path()).unwrap();
+        read_owner(&mut file).unwrap().0
+    }
+
+    #[test]
+    fn a_live_owner_is_refused() {
+        let me = std::process::id();
+        let owner = format!("{} {}", me, start_time(me).unwrap());
+        with_owner("live", &owner, || {
+            assert!(matches!(
+                acquire(false),
+                Err(LockError::AlreadyRunning { pid: Some(pid) }) if pid == me
+            ));
+        });
+    }
+
+    #[test]
+    fn a_dead_owner_is_reclaimed() {
+        // Above the kernel's PID limit, so never running
+        with_owner("dead", "4194305 1", || {
+            acquire(false).unwrap();
+            assert_eq!(recorded_pid(), std::process::id());
+        });
+    }
+
+    #[test]
+    fn a_reused_pid_is_reclaimed() {
+        let me = std::process::id();
+        let owner = format!("{} {}", me, start_time(me).unwrap() + 1);
+        with_owner("reused", &owner, || {
+            acquire(false).unwrap();
+            assert_eq!(recorded_pid(), me);
+        });
+    }
+
+    #[test]
+    fn force_takes_over_a_live_owner() {
+        let me = std::process::id();
+        let owner = format!("{} {}", me, start_time(me).unwrap());
+        with_owner("force", &owner, || {
+            acquire(true).unwrap();
+            assert_eq!(recorded_pid(), me);
+
+            // Released, the file stays and the next start takes it
+            release();
+            assert!(lock_path().exists());
+            acquire(false).unwrap();
+        });
+    }
+}
@@ -0,0 +1,586 @@
+use std::env;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+use std::os::fd::{AsRawFd, OwnedFd};
+use std::path::PathBuf;
+use std::process::{Child, Command};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+use zbus::blocking::{Connection, Proxy};
+use zbus::zvariant::{self, OwnedObjectPath};
+
+use crate::ready;
+
+const LOGIND_SERVICE: &str = "org.freedesktop.login1";
+const LOGIND_PATH: &str = "/org/freedesktop/login1";
+const LOGIND_MANAGER: &str = "org.freedesktop.login1.Manager";
+const LOGIND_SESSION: &str = "org.freedesktop.login1.Session";
+
+// How often the daemon looks at its child while nothing else happens
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+// How long sleep is held up for the lock screen, logind's own limit
+// (InhibitDelayMaxSec) defaults to 5 seconds
+const SLEEP_LOCK_TIMEOUT: Duration = Duration::from_secs(4);
+
+// Set from the SIGUSR1 and SIGUSR2 handlers, see `watch_unlock_signal`
+static UNLOCK_REQUESTED: AtomicBool = AtomicBool::new(false);
+static GRACE_CANCELLED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug)]
+pub enum LogindError {
+    Bus(zbus::Error),
+    Spawn(io::Error),
+}
+
+impl fmt::Display for LogindError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogindError::Bus(e) => write!(f, "logind: {}", e),
+            LogindError::Spawn(e) => write!(f, "could not start the lock screen: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for LogindError {}
+
+impl From<zbus::Error> for LogindError {
+    fn from(e: zbus::Error) -> Self {
+        LogindError::Bus(e)
+    }
+}
+
+// What the signal threads pass on to the daemon loop
+enum Signal {
+    Lock,
+    Unlock,
+    PrepareForSleep(bool),
+}
+
+/// Runs until the bus goes away, starting a lock screen with `lock_args`
+/// whenever logind asks for the session to be locked or the machine is
+/// about to sleep.
+pub fn run_daemon(lock_args: Vec<OsString>) -> Result<(), LogindError> {
+    let connection = Connection::system()?;
+    let program = env::current_exe().map_err(LogindError::Spawn)?;
+    serve(&connection, program, lock_args)
+}
+
+// The daemon loop, with logind on `connection` and `program` as the lock
+// screen
+fn serve(
+    connection: &Connection,
+    program: PathBuf,
+    lock_args: Vec<OsString>,
+) -> Result<(), LogindError> {
+    let manager = Proxy::new(connection, LOGIND_SERVICE, LOGIND_PATH, LOGIND_MANAGER)?;
+    // "auto" is the session we run in, or the user's display session
+    let session_id = env::var("XDG_SESSION_ID").unwrap_or_else(|_| "auto".to_string());
+    let session_path: OwnedObjectPath = manager.call("GetSession", &(session_id,))?;
+    let session = Proxy::new(
+        connection,
+        LOGIND_SERVICE,
+        session_path.into_inner(),
+        LOGIND_SESSION,
+    )?;
+
+    let (tx, rx) = mpsc::channel();
+    forward(&session, "Lock", tx.clone(), |_| Some(Signal::Lock))?;
+    forward(&session, "Unlock", tx.clone(), |_| Some(Signal::Unlock))?;
+    forward(&manager, "PrepareForSleep", tx, |message| {
+        message
+            .body()
+            .deserialize::<bool>()
+            .ok()
+            .map(Signal::PrepareForSleep)
+    })?;
+
+    let mut daemon = Daemon {
+        program,
+        lock_args,
+        session,
+        child: None,
+        ready: None,
+        locked: false,
+    };
+    let mut inhibitor = take_inhibitor(&manager);
+    ready::signal();
+    loop {
+        let signal = match rx.recv_timeout(POLL_INTERVAL) {
+            Ok(signal) => Some(signal),
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => return Ok(()),
+        };
+        match signal {
+            Some(Signal::Lock) => daemon.lock(true)?,
+            Some(Signal::Unlock) => daemon.unlock(),
+            Some(Signal::PrepareForSleep(true)) => {
+                // Waking up must never unlock without the password
+                daemon.lock(false)?;
+                if !daemon.wait_locked(SLEEP_LOCK_TIMEOUT) {
+                    eprintln!("raylock: the lock screen wasn't up in time, sleeping anyway");
+                }
+                daemon.end_grace();
+                // Dropping the descriptor lets the machine go to sleep
+                inhibitor = None;
+            }
+            Some(Signal::PrepareForSleep(false)) if inhibitor.is_none() => {
+                inhibitor = take_inhibitor(&manager);
+            }
+            Some(Signal::PrepareForSleep(false)) | None => {}
+        }
+        daemon.check_child();
+    }
+}
+
+// Passes `name` signals from `proxy` on to the daemon loop from a thread of
+// their own, the blocking iterators can't be waited on together
+fn forward(
+    proxy: &Proxy<'static>,
+    name: &'static str,
+    tx: Sender<Signal>,
+    convert: fn(&zbus::Message) -> Option<Signal>,
+) -> Result<(), LogindError> {
+    let signals = proxy.receive_signal(name)?;
+    thread::spawn(move || {
+        for message in signals {
+            if let Some(signal) = convert(&message) {
+                if tx.send(signal).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+    Ok(())
+}
+
+// Holds up sleep until it's dropped. Without one the daemon still locks on
+// suspend, just maybe too late for the lock screen to be up first.
+fn take_inhibitor(manager: &Proxy) -> Option<zvariant::OwnedFd> {
+    let inhibitor = manager.call(
+        "Inhibit",
+        &(
+            "sleep",
+            "raylock",
+            "Lock the screen before sleeping",
+            "delay",
+        ),
+    );
+    match inhibitor {
+        Ok(fd) => Some(fd),
+        Err(e) => {
+            eprintln!("raylock: could not take a sleep inhibitor: {}", e);
+            None
+        }
+    }
+}
+
+struct Daemon {
+    program: PathBuf,
+    lock_args: Vec<OsString>,
+    session: Proxy<'static>,
+    child: Option<Child>,
+    // The child's --ready-fd pipe, until it reports the session locked
+    ready: Option<File>,
+    // Whether the child has reported the session locked
+    locked: bool,
+}
+
+impl Daemon {
+    fn lock(&mut self, grace: bool) -> Result<(), LogindError> {
+        if self.child.is_some() {
+            return Ok(());
+        }
+        let (reader, writer) = ready::pipe().map_err(LogindError::Spawn)?;
+        set_nonblocking(&reader).map_err(LogindError::Spawn)?;
+        // Only the write end goes to the child. Nothing else is started
+        // from this thread, so it can't leak anywhere else.
+        unsafe { libc::fcntl(writer.as_raw_fd(), libc::F_SETFD, 0) };
+        let child = Command::new(&self.program)
+            .args(&self.lock_args)
+            .arg("--unlock-signal")
+            .args((!grace).then_some("--no-grace"))
+            .arg("--ready-fd")
+            .arg(writer.as_raw_fd().to_string())
+            .spawn()
+            .map_err(LogindError::Spawn)?;
+        drop(writer);
+        self.child = Some(child);
+        self.ready = Some(File::from(reader));
+        Ok(())
+    }
+
+    // Only once it's reported locked, like `end_grace`. An Unlock before
+    // then is dropped and the password is needed.
+    fn unlock(&mut self) {
+        self.check_child();
+        if let (Some(child), true) = (&self.child, self.locked) {
+            unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGUSR1) };
+        }
+    }
+
+    // For a lock screen started before a sleep. Only once it's reported
+    // locked, until then it may not handle SIGUSR2 yet and would die of it.
+    fn end_grace(&self) {
+        if let (Some(child), true) = (&self.child, self.locked) {
+            unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGUSR2) };
+        }
+    }
+
+    // Waits for the lock screen to report the session locked, false if it
+    // didn't within `timeout`
+    fn wait_locked(&mut self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        while Instant::now() < deadline {
+            self.check_child();
+            if self.locked {
+                return true;
+            }
+            if self.child.is_none() {
+                return false;
+            }
+            thread::sleep(POLL_INTERVAL / 2);
+        }
+        false
+    }
+
+    // Follows the child's lock into LockedHint, and notices it exiting
+    fn check_child(&mut self) {
+        let Some(child) = &mut self.child else {
+            return;
+        };
+        match child.try_wait() {
+            Ok(None) => {
+                if self.ready.as_mut().is_some_and(reported_ready) {
+                    self.ready = None;
+                    self.locked = true;
+                    self.set_locked_hint(true);
+                }
+            }
+            Ok(Some(status)) => {
+                if !status.success() {
+                    eprintln!("raylock: the lock screen exited with {}", status);
+                }
+                self.child = None;
+                self.ready = None;
+                if self.locked {
+                    self.locked = false;
+                    self.set_locked_hint(false);
+                }
+            }
+            Err(e) => eprintln!("raylock: lost track of the lock screen: {}", e),
+        }
+    }
+
+    fn set_locked_hint(&self, locked: bool) {
+        if let Err(e) = self.session.call::<_, _, ()>("SetLockedHint", &(locked,)) {
+            eprintln!("raylock: could not set LockedHint: {}", e);
+        }
+    }
+}
+
+// Whether the child wrote to its --ready-fd yet
+fn reported_ready(ready: &mut File) -> bool {
+    let mut byte = [0u8; 1];
+    matches!(ready.read(&mut byte), Ok(1))
+}
+
+fn set_nonblocking(fd: &OwnedFd) -> io::Result<()> {
+    let flags = unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_GETFL) };
+    if flags == -1
+        || unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_SETFL, flags | libc::O_NONBLOCK) } == -1
+    {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+extern "C" fn on_unlock_signal(signal: libc::c_int) {
+    match signal {
+        libc::SIGUSR1 => UNLOCK_REQUESTED.store(true, Ordering::SeqCst),
+        _ => GRACE_CANCELLED.store(true, Ordering::SeqCst),
+    }
+}
+
+/// Lets the daemon pass on logind's Unlock as SIGUSR1, and end the grace
+/// period with SIGUSR2 when the machine is about to sleep
+pub fn watch_unlock_signal() {
+    let handler: extern "C" fn(libc::c_int) = on_unlock_signal;
+    unsafe {
+        libc::signal(libc::SIGUSR1, handler as libc::sighandler_t);
+        libc::signal(libc::SIGUSR2, handler as libc::sighandler_t);
+    }
+}
+
+/// Whether an unlock arrived since the last call
+pub fn unlock_requested() -> bool {
+    UNLOCK_REQUESTED.swap(false, Ordering::SeqCst)
+}
+
+pub fn grace_cancelled() -> bool {
+    GRACE_CANCELLED.swap(false, Ordering::SeqCst)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::net::UnixStream;
+    use std::sync::mpsc::Receiver;
+    use std::sync::{Mutex, MutexGuard};
+    use zbus::blocking::connection::Builder;
+    use zbus::fdo;
+
+    const SESSION_PATH: &str = "/org/freedesktop/login1/session/test";
+
+    // One daemon at a time. A lock screen started by one test would
+    // otherwise inherit descriptors another test's daemon has just been
+    // handed, like its inhibitor, and keep them open.
+    static ONE_AT_A_TIME: Mutex<()> = Mutex::new(());
+    const PATIENCE: Duration = Duration::from_secs(5);
+
+    // Stands in for raylock. Logs its arguments and the signals it gets,
+    // and reports ready once the gate file exists, if it was given one.
+    // Gives up once the test removed its directory.
+    const LOCK_SCREEN: &str = r#"#!/bin/sh
+log=$1
+gate=$2
+shift 2
+trap 'echo unlocked >> "$log"; exit 0' USR1
+trap 'echo grace-ended >> "$log"' USR2
+echo "started $*" >> "$log"
+while [ -n "$gate" ] && [ ! -e "$gate" ] && [ -e "$log" ]; do sleep 0.02; done
+eval "fd=\${$#}"
+printf x > /proc/$$/fd/$fd
+while [ -e "$log" ]; do sleep 0.02; done
+"#;
+
+    /// What the fake logind saw, in order
+    #[derive(Debug, PartialEq)]
+    enum Seen {
+        Inhibit,
+        InhibitorReleased,
+        LockedHint(bool),
+    }
+
+    struct Manager {
+        seen: Sender<Seen>,
+    }
+
+    #[zbus::interface(name = "org.freedesktop.login1.Manager")]
+    impl Manager {
+        fn get_session(&self, _id: String) -> OwnedObjectPath {
+            OwnedObjectPath::try_from(SESSION_PATH).unwrap()
+        }
+
+        // The daemon gets the write end of a pipe, the read end tells when
+        // it let go of it
+        fn inhibit(
+            &self,
+            _what: String,
+            _who: String,
+            _why: String,
+            _mode: String,
+        ) -> fdo::Result<zvariant::OwnedFd> {
+            let (reader, writer) = ready::pipe().map_err(|e| fdo::Error::Failed(e.to_string()))?;
+            let seen = self.seen.clone();
+            thread::spawn(move || {
+                let mut rest = Vec::new();
+                let _ = File::from(reader).read_to_end(&mut rest);
+                let _ = seen.send(Seen::InhibitorReleased);
+            });
+            let _ = self.seen.send(Seen::Inhibit);
+            Ok(writer.into())
+        }
+    }
+
+    struct Session {
+        seen: Sender<Seen>,
+    }
+
+    #[zbus::interface(name = "org.freedesktop.login1.Session")]
+    impl Session {
+        fn set_locked_hint(&self, locked: bool) {
+            let _ = self.seen.send(Seen::LockedHint(locked));
+        }
+    }
+
+    struct Harness {
+        logind: Connection,
+        seen: Receiver<Seen>,
+        dir: PathBuf,
+        // Last, so the next test waits for the fake logind to go
+        _turn: MutexGuard<'static, ()>,
+    }
+
+    impl Harness {
+        /// Starts the daemon against a fake logind, peer to peer, with the
+        /// fake lock screen. With `gated` the lock screen waits for
+        /// `open_gate` before reporting ready.
+        fn start(name: &str, gated: bool) -> Harness {
+            let turn = ONE_AT_A_TIME.lock().unwrap_or_else(|e| e.into_inner());
+            let dir =
+                env::temp_dir().join(format!("raylock-daemon-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            let program = dir.join("lock-screen");
+            fs::write(&program, LOCK_SCREEN).unwrap();
+            fs::set_permissions(&program, fs::Permissions::from_mode(0o755)).unwrap();
+
+            let (ours, theirs) = UnixStream::pair().unwrap();
+            let (seen, received) = mpsc::channel();
+            let manager = Manager { seen: seen.clone() };
+            let logind = thread::spawn(move || {
+                Builder::unix_stream(theirs)
+                    .server(zbus::Guid::generate())
+                    .unwrap()
+                    .p2p()
+                    .serve_at(LOGIND_PATH, manager)
+                    .unwrap()
+                    .serve_at(SESSION_PATH, Session { seen })
+                    .unwrap()
+                    .build()
+                    .unwrap()
+            });
+            let client = Builder::unix_stream(ours).p2p().build().unwrap();
+            let logind = logind.join().unwrap();
+
+            let lock_args = vec![
+                dir.join("log").into_os_string(),
+                if gated {
+                    dir.join("gate").into_os_string()
+                } else {
+                    OsString::new()
+                },
+            ];
+            thread::spawn(move || serve(&client, program, lock_args));
+            let harness = Harness {
+                logind,
+                seen: received,
+                dir,
+                _turn: turn,
+            };
+            // Taken after the signals are subscribed to
+            harness.expect(Seen::Inhibit);
+            harness
+        }
+
+        fn emit<B>(&self, path: &str, interface: &str, name: &str, body: &B)
+        where
+            B: serde::Serialize + zvariant::DynamicType,
+        {
+            self.logind
+                .emit_signal(None::<()>, path, interface, name, body)
+                .unwrap();
+        }
+
+        fn lock(&self) {
+            self.emit(SESSION_PATH, LOGIND_SESSION, "Lock", &());
+        }
+
+        fn unlock(&self) {
+            self.emit(SESSION_PATH, LOGIND_SESSION, "Unlock", &());
+        }
+
+        fn prepare_for_sleep(&self, sleeping: bool) {
+            self.emit(LOGIND_PATH, LOGIND_MANAGER, "PrepareForSleep", &(sleeping,));
+        }
+
+        fn expect(&self, expected: Seen) {
+            assert_eq!(self.seen.recv_timeout(PATIENCE).unwrap(), expected);
+        }
+
+        fn open_gate(&self) {
+            fs::write(self.dir.join("gate"), "").unwrap();
+        }
+
+        fn log(&self) -> Vec<String> {
+            fs::read_to_string(self.dir.join("log"))
+                .unwrap_or_default()
+                .lines()
+                .map(str::to_string)
+                .collect()
+        }
+
+        // Waits for the lock screen to have logged `count` lines
+        fn wait_for_log(&self, count: usize) -> Vec<String> {
+            let deadline = Instant::now() + PATIENCE;
+            loop {
+                let log = self.log();
+                if log.len() >= count || Instant::now() > deadline {
+                    return log;
+                }
+                thread::sleep(POLL_INTERVAL / 4);
+            }
+        }
+    }
+
+    impl Drop for Harness {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn started(args: &str, log: &str) -> bool {
+        log.strip_prefix("started ")
+            .is_some_and(|rest| rest.starts_with(args) && rest.contains("--ready-fd"))
+    }
+
+    #[test]
+    fn lock_starts_the_lock_screen_and_unlock_ends_it() {
+        let harness = Harness::start("lock", false);
+        harness.lock();
+        harness.expect(Seen::LockedHint(true));
+        let log = harness.log();
+        assert!(started("--unlock-signal --ready-fd", &log[0]), "{:?}", log);
+
+        harness.unlock();
+        harness.expect(Seen::LockedHint(false));
+        assert_eq!(harness.wait_for_log(2)[1], "unlocked");
+    }
+
+    #[test]
+    fn sleep_waits_for_the_lock_screen_without_grace() {
+        let harness = Harness::start("sleep", true);
+        harness.prepare_for_sleep(true);
+        // Sleep is held up until the lock screen is ready
+        thread::sleep(POLL_INTERVAL * 3);
+        assert!(harness.seen.try_recv().is_err());
+        harness.open_gate();
+        harness.expect(Seen::LockedHint(true));
+        harness.expect(Seen::InhibitorReleased);
+        let log = harness.wait_for_log(2);
+        assert!(
+            started("--unlock-signal --no-grace --ready-fd", &log[0]),
+            "{:?}",
+            log
+        );
+        assert_eq!(log[1], "grace-ended");
+
+        // A fresh inhibitor for the next sleep
+        harness.prepare_for_sleep(false);
+        harness.expect(Seen::Inhibit);
+        harness.unlock();
+        harness.expect(Seen::LockedHint(false));
+    }
+
+    #[test]
+    fn unlock_before_the_lock_screen_is_ready_is_dropped() {
+        let harness = Harness::start("early", true);
+        harness.lock();
+        harness.wait_for_log(1);
+        harness.unlock();
+        thread::sleep(POLL_INTERVAL * 3);
+        assert_eq!(harness.log().len(), 1, "{:?}", harness.log());
+
+        harness.open_gate();
+        harness.expect(Seen::LockedHint(true));
+        harness.unlock();
+        harness.expect(Seen::LockedHint(false));
+        assert_eq!(harness.wait_for_log(2)[1], "unlocked");
+    }
+}
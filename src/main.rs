@@ -1,108 +1,415 @@
-use configs::SCREEN_HEIGHT;
-use eframe::Error;
-use eframe::{egui, App};
-use egui::FontFamily;
-use egui::Key;
+use eframe::egui;
 // use graph::CpuGraph;
-use input::is_locked;
-use panes::Pane;
 // use serde::de::Error;
 // use rand::Error;
 // use panes::{PaneConfig, PaneRenderer, SplitDirection};
-use std::collections::BTreeMap;
-use std::collections::HashMap;
-use std::process::{Command, Stdio};
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 // use egui::epaint::text::{FontInsert, InsertFontFamily};
 
-use eframe::CreationContext;
-use egui::ecolor::Color32;
-use egui::FontId;
-use egui::Stroke;
 // use egui_terminal::prelude::*;
 // use egui_terminal::render::CursorType;
 
-use egui::ecolor::HexColor;
-
-mod configs;
+mod auth;
+mod backoff;
+mod cli;
+mod control;
+mod events;
+mod hooks;
+mod idle;
+mod settings;
+mod shadow;
 
 mod cpugraph;
 mod diskgraph;
 mod graph;
 mod memgraph;
 mod netgraph;
+mod sampler;
 
 mod table;
+mod theme;
 
+mod indicators;
 mod infopane;
 
 mod input;
+mod keyboard;
+mod keys;
+mod lockfile;
+mod logind;
+mod pam;
 mod panes;
+mod power;
+mod ready;
+mod secret;
+mod session_lock;
 mod structs;
+mod sway_ipc;
 mod ui;
 
-use panes::{load_pane_config, EXAMPLE_CONFIG};
+// Longest a frame waits when nothing changes, ticks with the clock
+const IDLE_INTERVAL: Duration = Duration::from_secs(1);
+// How often to look for the auth thread picking up a submitted password
+const VERIFY_INTERVAL: Duration = Duration::from_millis(100);
+// Frame rate of the lockout countdown
+const COUNTDOWN_INTERVAL: Duration = Duration::from_millis(100);
+// Frame rate with the outputs off, only for the odd state change
+const POWERED_OFF_INTERVAL: Duration = Duration::from_secs(5);
+// A sample is published a moment after it's due
+const SAMPLE_SLACK: Duration = Duration::from_millis(20);
 
 struct ExampleApp {
     auth_state: Arc<Mutex<structs::AuthState>>,
-    root_pane: panes::PaneInstance,
+    theme: theme::Theme,
+    settings: settings::Settings,
+    // --layout, used by outputs without their own layout
+    default_layout: Option<PathBuf>,
+    // The window mode only covers one output, built on the first frame
+    window_screen: Option<panes::Screen>,
+    // Kept up to date by the frontend
+    keyboard: indicators::KeyboardStatus,
+    power_menu: Option<power::Menu>,
+    // Last seen during the grace period
+    pointer: Option<egui::Pos2>,
+    // Unlocked and waiting for the window to close
+    closing: bool,
 }
 
 impl ExampleApp {
     fn name() -> &'static str {
         "raylock"
     }
-}
 
-impl eframe::App for ExampleApp {
-    fn clear_color(&self, _visuals: &egui::Visuals) -> [f32; 4] {
-        egui::Rgba::from_rgba_unmultiplied(0.1, 0.1, 0.1, 0.9).to_array()
+    fn clear_color(&self) -> [f32; 4] {
+        self.theme.clear_color()
     }
 
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+    fn attempts(&self) -> backoff::Record {
+        self.auth_state.lock().unwrap().attempts()
+    }
+
+    fn is_unlocked(&self) -> bool {
+        self.auth_state.lock().unwrap().unlocked()
+    }
+
+    // Input is applied once per frame here rather than inside `show`, so the
+    // session lock frontend can draw several outputs without typing twice
+    //
+    // In the window mode characters come from Text and IME commits, which
+    // winit already ran through the keyboard layout, the session lock sends
+    // them to `key_input`. Key events are only for the editing commands in
+    // `settings.keys`.
+    fn handle_events(&mut self, events: &[egui::Event]) {
+        // The key that wakes the screen is typed like any other
+        if !events.is_empty() {
+            idle::activity();
+        }
+        if self.grace_unlock(events) {
+            return;
+        }
+        let events = self.handle_power_keys(events);
         let mut state = self.auth_state.lock().unwrap();
-        if ctx.input(|i| i.events.len() > 0) {
-            ctx.input(|i| {
-                for event in &i.events {
-                    if let egui::Event::Key {
-                        key,
-                        pressed,
-                        modifiers,
-                        ..
-                    } = event
-                    {
-                        if *pressed {
-                            match key {
-                                Key::Enter => {
-                                    state.to_be_submitted = true;
-                                }
-                                Key::Backspace => {
-                                    let len = state.password.len();
-                                    if len != 0 {
-                                        state.password.remove(len - 1 as usize);
-                                    }
-                                }
-                                _ => {
-                                    let str = &input::format_key(*key, modifiers.shift);
-                                    state.password += str;
-                                }
-                            }
+        if !state.accepts_input() {
+            return;
+        }
+        for event in &events {
+            match event {
+                egui::Event::Text(text) | egui::Event::Ime(egui::ImeEvent::Commit(text)) => {
+                    for ch in text.chars().filter(|ch| !ch.is_control()) {
+                        if !state.type_char(ch) {
+                            break;
                         }
                     }
                 }
-            });
+                egui::Event::Key {
+                    key,
+                    pressed: true,
+                    modifiers,
+                    ..
+                } => match self.settings.keys.action(*key, *modifiers) {
+                    Some(keys::Action::Submit) => state.to_be_submitted = true,
+                    Some(keys::Action::DeleteChar) => {
+                        state.password.pop();
+                        state.full = false;
+                    }
+                    Some(keys::Action::DeleteWord) => {
+                        state.password.pop_word();
+                        state.full = false;
+                    }
+                    Some(keys::Action::Clear) => state.clear_password(),
+                    Some(keys::Action::PowerMenu) | None => {}
+                },
+                _ => {}
+            }
+        }
+    }
+
+    /// Keys from the session lock's own keyboard, handled as they arrive.
+    /// Characters go straight into the password rather than through the
+    /// String of a `Text` event.
+    pub fn key_input(&mut self, input: keyboard::Input) {
+        match input {
+            keyboard::Input::Event(event) => self.handle_events(&[event]),
+            keyboard::Input::Char(ch) => {
+                idle::activity();
+                if self.end_grace() || self.power_menu.is_some() {
+                    return;
+                }
+                let mut state = self.auth_state.lock().unwrap();
+                if state.accepts_input() {
+                    state.type_char(ch);
+                }
+            }
+        }
+    }
+
+    fn grace_unlock(&mut self, events: &[egui::Event]) -> bool {
+        if self.auth_state.lock().unwrap().grace_left().is_none() {
+            return false;
+        }
+        let mut touched = false;
+        for event in events {
+            touched |= match event {
+                // Entering the window reports where the pointer already
+                // was, only a change of position is someone moving it
+                egui::Event::PointerMoved(pos) => {
+                    self.pointer.replace(*pos).is_some_and(|last| last != *pos)
+                }
+                egui::Event::Key { pressed: true, .. }
+                | egui::Event::PointerButton { pressed: true, .. }
+                | egui::Event::MouseWheel { .. } => true,
+                _ => false,
+            };
+        }
+        touched && self.end_grace()
+    }
+
+    fn end_grace(&mut self) -> bool {
+        let mut state = self.auth_state.lock().unwrap();
+        if state.grace_left().is_none() {
+            return false;
+        }
+        state.grace = None;
+        state.clear_password();
+        state.phase = structs::AuthPhase::Succeeded(Instant::now());
+        backoff::clear();
+        true
+    }
+
+    // Opens, drives and closes the power menu. Returns the events left for
+    // the password, which is none of them while the menu is open. It works
+    // during a lockout too, suspending shouldn't have to wait on one.
+    fn handle_power_keys(&mut self, events: &[egui::Event]) -> Vec<egui::Event> {
+        let mut rest = Vec::new();
+        for event in events {
+            let egui::Event::Key {
+                key,
+                pressed: true,
+                modifiers,
+                ..
+            } = event
+            else {
+                if self.power_menu.is_none() {
+                    rest.push(event.clone());
+                }
+                continue;
+            };
+            let action = self.settings.keys.action(*key, *modifiers);
+            let picked = self.settings.power.pick(*key, *modifiers);
+
+            self.power_menu = match (self.power_menu.take(), action, picked) {
+                (None, Some(keys::Action::PowerMenu), _) => Some(power::Menu::Choosing),
+                (None, _, Some(picked)) => Some(power::Menu::Confirming(picked)),
+                (None, _, None) => {
+                    rest.push(event.clone());
+                    None
+                }
+                // logind has it, there is nothing left to cancel
+                (Some(menu @ power::Menu::Requested(..)), ..) => Some(menu),
+                (Some(_), Some(keys::Action::Clear | keys::Action::PowerMenu), _) => None,
+                (Some(power::Menu::Confirming(chosen)), Some(keys::Action::Submit), _) => Some(
+                    power::Menu::confirm(chosen, zbus::blocking::Connection::system),
+                ),
+                (Some(_), _, Some(picked)) => Some(power::Menu::Confirming(picked)),
+                (menu, ..) => menu,
+            };
+        }
+        rest
+    }
+
+    /// Collects logind's answer to a power request, true when the menu
+    /// changed and needs a frame
+    fn poll_power_menu(&mut self) -> bool {
+        if !self.power_menu.as_ref().is_some_and(power::Menu::waiting) {
+            return false;
+        }
+        self.power_menu = self.power_menu.take().and_then(power::Menu::poll);
+        !self.power_menu.as_ref().is_some_and(power::Menu::waiting)
+    }
+
+    /// `size` is the output's current mode in pixels
+    fn screen_for(&self, name: Option<&str>, size: Option<(i32, i32)>) -> panes::Screen {
+        let layout = self
+            .settings
+            .output_layout(name, size)
+            .map(Path::to_path_buf)
+            .or_else(|| self.default_layout.clone());
+        panes::Screen::new(panes::load_layout(layout))
+    }
+
+    // The ring goes on the primary output, or everywhere when that isn't
+    // connected so there is always one to type into
+    fn shows_ring(&self, name: Option<&str>, primary_connected: bool) -> bool {
+        match &self.settings.primary_output {
+            Some(primary) => !primary_connected || name == Some(primary.as_str()),
+            None => true,
         }
+    }
+
+    /// How long the next frame can wait. Input repaints right away on its
+    /// own, this covers everything that changes without input.
+    fn repaint_after(&self, screen: &panes::Screen) -> Duration {
+        // Nothing to see, input wakes the screen and asks for a frame itself
+        if idle::powered_off() {
+            return POWERED_OFF_INTERVAL;
+        }
+        if self.power_menu.as_ref().is_some_and(power::Menu::waiting) {
+            return VERIFY_INTERVAL;
+        }
+        let state = self.auth_state.lock().unwrap();
+        if state.to_be_submitted {
+            return VERIFY_INTERVAL;
+        }
+        // The spinner, the verdicts and the grace period animate
+        let ring_animating = state.verifying()
+            || state.phase.result_progress().is_some()
+            || state.clear_progress().is_some()
+            || state.grace_left().is_some();
+        if !ring_animating && state.attempts().locked_out() {
+            return COUNTDOWN_INTERVAL;
+        }
+        drop(state);
 
-        // let mut pane = ;
+        if ring_animating || screen.animating() || idle::fading() {
+            return Duration::ZERO;
+        }
+
+        let since_epoch = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let next_second = IDLE_INTERVAL - Duration::from_nanos(since_epoch.subsec_nanos().into());
+        let next_sample = sampler::get()
+            .next_due()
+            .map(|due| due.saturating_duration_since(Instant::now()) + SAMPLE_SLACK);
+        [next_sample, idle::until_dim()]
+            .into_iter()
+            .flatten()
+            .fold(next_second, Duration::min)
+    }
+
+    fn show(&self, ctx: &egui::Context, screen: &mut panes::Screen, show_ring: bool) {
+        screen.fit(ctx.screen_rect(), ctx.pixels_per_point(), &self.theme);
+        let state = self.auth_state.lock().unwrap();
         egui::CentralPanel::default()
             .frame(egui::Frame::none())
             .show(ctx, |ui| {
-                std::thread::sleep(Duration::from_millis(50));
-                ui::update(state, ctx, _frame, ui, &mut self.root_pane);
-                ctx.request_repaint();
+                ui::update(
+                    state,
+                    ui,
+                    screen.root_pane(),
+                    &self.theme,
+                    show_ring,
+                    &self.keyboard,
+                );
+            });
+        let overlay = ctx.layer_painter(egui::LayerId::new(
+            egui::Order::Foreground,
+            egui::Id::new("overlay"),
+        ));
+        let dim = idle::dim_level();
+        if dim > 0. {
+            overlay.rect_filled(
+                ctx.screen_rect(),
+                0.,
+                egui::Color32::from_black_alpha((dim * 255.) as u8),
+            );
+        }
+        if let (true, Some(menu)) = (show_ring, &self.power_menu) {
+            ui::paint_menu(
+                &menu.lines(&self.settings.power),
+                ctx.screen_rect(),
+                &overlay,
+                &self.theme,
+            );
+        }
+        if show_ring {
+            // Asks the frontend to turn on the IME, with its popup by the ring
+            let ring = egui::Rect::from_center_size(
+                ctx.screen_rect().center(),
+                egui::Vec2::splat(self.theme.login.circle_radius * 2.),
+            );
+            ctx.output_mut(|output| {
+                output.ime = Some(egui::output::IMEOutput {
+                    rect: ring,
+                    cursor_rect: ring,
+                })
             });
+        }
+        ctx.request_repaint_after(self.repaint_after(screen));
+    }
+}
+
+impl eframe::App for ExampleApp {
+    fn clear_color(&self, _visuals: &egui::Visuals) -> [f32; 4] {
+        ExampleApp::clear_color(self)
+    }
+
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        if self.is_unlocked() {
+            // The unlock hooks are waited for once the window is gone
+            if !self.closing {
+                self.closing = true;
+                idle::restore();
+                input::sway_unlock_input();
+                lockfile::release();
+                control::close();
+                events::emit(events::Event::Unlock, &self.attempts());
+            }
+            ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+            return;
+        }
+
+        // The first frame is on screen once the second one starts, and
+        // sway's lock mode was confirmed before the window opened
+        if self.window_screen.is_some() {
+            ready::signal();
+        } else {
+            ctx.request_repaint();
+        }
+
+        let events = ctx.input(|i| i.events.clone());
+        self.handle_events(&events);
+        self.poll_power_menu();
+
+        // Reading sysfs every frame would stall the UI, the sampler polls it
+        let sampler = sampler::get();
+        sampler.want(sampler::Metric::Leds);
+        let (caps_lock, num_lock) = sampler.leds.load().value;
+        self.keyboard = indicators::KeyboardStatus {
+            caps_lock,
+            num_lock,
+            layout: input::sway_layout(),
+        };
+
+        let mut screen = self
+            .window_screen
+            .take()
+            .unwrap_or_else(|| self.screen_for(None, None));
+        self.show(ctx, &mut screen, true);
+        self.window_screen = Some(screen);
     }
 }
 
@@ -112,92 +419,242 @@ fn main() -> eframe::Result<()> {
         ..eframe::NativeOptions::default()
     };
 
+    let args = cli::Args::parse();
+    // Before anything starts a thread, fork only keeps the calling one
+    if args.daemonize {
+        if let Err(e) = ready::daemonize() {
+            eprintln!("raylock: could not fork: {}", e);
+            std::process::exit(1);
+        }
+    }
+    if let Some(fd) = args.ready_fd {
+        if let Err(e) = ready::set_fd(fd) {
+            eprintln!("raylock: --ready-fd {}: {}", fd, e);
+            std::process::exit(2);
+        }
+    }
+    if args.daemon {
+        // Only the lock screens it starts need root, for /etc/shadow
+        if let Err(e) = shadow::drop_privileges() {
+            eprintln!("raylock: {}", e);
+            std::process::exit(1);
+        }
+        if let Err(e) = logind::run_daemon(cli::lock_args()) {
+            eprintln!("raylock: {}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if args.unlock_signal {
+        logind::watch_unlock_signal();
+    }
+
+    // /etc/shadow is the only thing read with setuid root. The config, layout
+    // and theme are user supplied paths and only opened once it's dropped.
+    let shadow_hash = shadow::read_own_hash();
+    if let Err(e) = shadow::drop_privileges() {
+        eprintln!("raylock: {}", e);
+        std::process::exit(1);
+    }
+    let settings = settings::Settings::load(&args);
+
+    let mut authenticator = match auth::create(&settings.auth, shadow_hash) {
+        Ok(authenticator) => authenticator,
+        Err(e) => {
+            eprintln!(
+                "raylock: could not set up {:?} authentication: {}",
+                settings.auth.backend, e
+            );
+            std::process::exit(1);
+        }
+    };
+
+    let attempts = backoff::load();
     let state = Arc::new(Mutex::new(structs::AuthState {
-        password: String::new(),
-        to_be_submitted: false,
-        failed_attempts: 0,
+        failed_attempts: attempts.failed_attempts,
+        lockout: attempts.lockout,
+        ..Default::default()
     }));
+    let backoff = settings.backoff;
+    hooks::init(settings.hooks.clone());
 
     let auth_state_clone = state.clone();
 
+    // The state is only locked to read and write it, never while a check
+    // runs, so the frontend keeps drawing the spinner
     thread::spawn(move || loop {
-        let mut state = auth_state_clone.lock().unwrap();
+        let submitted = {
+            let mut state = auth_state_clone.lock().unwrap();
+            let submit = state.to_be_submitted && !state.attempts().locked_out();
+            state.to_be_submitted = false;
+            submit.then(|| {
+                // Saved as a failure until it's known not to be one, so
+                // killing raylock mid-check doesn't earn a free attempt
+                backoff::save(&backoff::Record {
+                    failed_attempts: state.failed_attempts.saturating_add(1),
+                    ..state.attempts()
+                });
+                state.phase = structs::AuthPhase::Verifying(Instant::now());
+                state.password.clone()
+            })
+        };
+        if let Some(password) = submitted {
+            authenticator.submit(&password);
+        }
 
-        if state.to_be_submitted {
-            let result = try_sudo(&state.password);
-            match result {
+        if logind::grace_cancelled() {
+            auth_state_clone.lock().unwrap().grace = None;
+        }
+        if logind::unlock_requested() {
+            let mut state = auth_state_clone.lock().unwrap();
+            state.phase = structs::AuthPhase::Succeeded(Instant::now());
+            state.error = None;
+            backoff::clear();
+        }
+
+        if let Some(verdict) = authenticator.result() {
+            let mut state = auth_state_clone.lock().unwrap();
+            let now = Instant::now();
+            match verdict {
                 Ok(true) => {
-                    // println!("True");
-                    input::sway_unlock_input();
-                    input::remove_lock();
-                    std::process::exit(0);
+                    state.phase = structs::AuthPhase::Succeeded(now);
+                    state.error = None;
+                    backoff::clear();
                 }
                 Ok(false) => {
-                    // println!("False");
-                    state.failed_attempts += 1;
-                    state.password.clear();
+                    state.phase = structs::AuthPhase::Failed(now);
+                    state.error = None;
+                    state.failed_attempts = state.failed_attempts.saturating_add(1);
+                    let delay = backoff.delay(state.failed_attempts);
+                    if !delay.is_zero() {
+                        state.lockout = Some((SystemTime::now() + delay, delay));
+                    }
+                    state.clear_password();
+                    backoff::save(&state.attempts());
+                    events::emit(events::Event::FailedAttempt, &state.attempts());
+                    if !delay.is_zero() {
+                        events::emit(events::Event::Lockout, &state.attempts());
+                    }
                 }
-                Err(_) => {
-                    state.password.clear();
+                Err(e) => {
+                    auth::log_error(&e);
+                    state.phase = structs::AuthPhase::Error(now);
+                    state.error = Some(e.kind());
+                    state.clear_password();
+                    // The backend never answered, that attempt doesn't count
+                    backoff::save(&state.attempts());
                 }
             }
-            state.to_be_submitted = false;
         }
-        drop(state);
         thread::sleep(Duration::from_millis(100));
     });
 
-    if input::is_locked() {
-        println!("Raylock is already running!");
+    if let Err(e) = lockfile::acquire(args.force) {
+        // swayidle's before-sleep starts a second lock with --no-grace while
+        // the one from its timeout may still be in its grace period
+        if args.no_grace && matches!(e, lockfile::LockError::AlreadyRunning { .. }) {
+            match control::end_grace() {
+                Ok(()) => {
+                    ready::signal();
+                    std::process::exit(0);
+                }
+                Err(error) => {
+                    eprintln!("raylock: could not end the running lock's grace: {}", error)
+                }
+            }
+        }
+        eprintln!("raylock: {}", e);
         std::process::exit(1);
     }
+    // A restart mustn't hand someone who was guessing a free unlock
+    if settings.grace_secs > 0 && attempts.failed_attempts == 0 && !attempts.locked_out() {
+        state.lock().unwrap().grace =
+            Some((Instant::now(), Duration::from_secs(settings.grace_secs)));
+    }
+    // Status bars go without, the lock itself doesn't need it
+    if let Err(e) = control::start(state.clone()) {
+        eprintln!(
+            "raylock: could not open {}: {}",
+            control::socket_path().display(),
+            e
+        );
+    }
 
-    input::sway_lock_input();
-    input::create_lock();
+    let mut mode = settings.mode;
+    // A lock screen that doesn't hold input is worse than none
+    if mode == settings::LockMode::Window {
+        match input::sway_lock_input() {
+            Ok(()) => events::emit(events::Event::Lock, &state.lock().unwrap().attempts()),
+            Err(e @ sway_ipc::IpcError::TooManyOutputs(_)) => {
+                eprintln!("raylock: {}, using session-lock instead", e);
+                mode = settings::LockMode::SessionLock;
+            }
+            Err(e) => {
+                eprintln!("raylock: could not lock input: {}", e);
+                lockfile::release();
+                control::close();
+                std::process::exit(1);
+            }
+        }
+    }
 
+    // With a session lock the compositor stays locked if we panic, only the
+    // window mode has input to give back
     let default_panic = std::panic::take_hook();
     std::panic::set_hook(Box::new(move |info| {
-        input::sway_unlock_input();
-        input::remove_lock();
+        if mode == settings::LockMode::Window {
+            input::sway_unlock_input();
+        }
+        idle::restore();
+        lockfile::release();
+        control::close();
         default_panic(info);
     }));
 
-    let mut pane_config = load_pane_config(EXAMPLE_CONFIG).unwrap();
-    pane_config.precalc(egui::Rect {
-        min: egui::Pos2 { x: 0., y: 0. },
-        max: egui::Pos2 {
-            x: configs::SCREEN_WIDTH,
-            y: configs::SCREEN_HEIGHT,
-        },
-    });
+    sampler::start(settings.sampler);
+    idle::start(settings.idle);
+    let theme = theme::Theme::load(args.theme.clone());
 
-    eframe::run_native(
-        ExampleApp::name(),
-        native_options,
-        Box::new(|_| {
-            Ok(Box::<ExampleApp>::new(ExampleApp {
-                auth_state: state,
-                root_pane: pane_config,
-                // cpu_graph: CpuGraph::new(),
-            }))
-        }),
-    )
-}
+    let app = ExampleApp {
+        auth_state: state,
+        theme,
+        settings,
+        default_layout: args.layout.clone(),
+        window_screen: None,
+        keyboard: indicators::KeyboardStatus::default(),
+        power_menu: None,
+        pointer: None,
+        closing: false,
+        // cpu_graph: CpuGraph::new(),
+    };
 
-fn try_sudo(password: &str) -> Result<bool, std::io::Error> {
-    let mut child = Command::new("sudo")
-        .args(["-kS", "true"]) // Use -S to read password from stdin
-        .stdin(Stdio::piped())
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()?;
-    if let Some(mut stdin) = child.stdin.take() {
-        use std::io::Write;
-        writeln!(stdin, "{}", password)?;
-    }
-
-    match child.wait() {
-        Ok(status) => Ok(status.success()),
-        Err(e) => Err(e),
+    match mode {
+        settings::LockMode::Window => {
+            let result = eframe::run_native(
+                ExampleApp::name(),
+                native_options,
+                Box::new(|_| Ok(Box::<ExampleApp>::new(app))),
+            );
+            // Returns once unlocked, or if the window failed to open or was
+            // closed, same cleanup as the panic hook
+            idle::restore();
+            input::sway_unlock_input();
+            lockfile::release();
+            control::close();
+            hooks::finish();
+            result
+        }
+        settings::LockMode::SessionLock => {
+            let result = session_lock::run(app);
+            idle::restore();
+            lockfile::release();
+            control::close();
+            if let Err(e) = result {
+                eprintln!("raylock: {}", e);
+                std::process::exit(1);
+            }
+            hooks::finish();
+            Ok(())
+        }
     }
 }
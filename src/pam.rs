@@ -0,0 +1,375 @@
+use libloading::Library;
+use std::ffi::{CStr, CString};
+use std::fmt;
+use std::os::raw::{c_char, c_int, c_void};
+use std::ptr;
+
+use crate::secret::Secret;
+
+// libpam is loaded at runtime, so building raylock doesn't need the PAM
+// development files and a missing libpam is reported as an auth error.
+const LIBPAM: &str = "libpam.so.0";
+
+const PAM_SUCCESS: c_int = 0;
+const PAM_BUF_ERR: c_int = 5;
+const PAM_PERM_DENIED: c_int = 6;
+const PAM_AUTH_ERR: c_int = 7;
+const PAM_CRED_INSUFFICIENT: c_int = 8;
+const PAM_USER_UNKNOWN: c_int = 10;
+const PAM_MAXTRIES: c_int = 11;
+const PAM_NEW_AUTHTOK_REQD: c_int = 12;
+const PAM_ACCT_EXPIRED: c_int = 13;
+const PAM_CONV_ERR: c_int = 19;
+
+const PAM_PROMPT_ECHO_OFF: c_int = 1;
+const PAM_PROMPT_ECHO_ON: c_int = 2;
+const PAM_ERROR_MSG: c_int = 3;
+const PAM_TEXT_INFO: c_int = 4;
+
+#[repr(C)]
+struct PamMessage {
+    msg_style: c_int,
+    msg: *const c_char,
+}
+
+#[repr(C)]
+struct PamResponse {
+    resp: *mut c_char,
+    resp_retcode: c_int,
+}
+
+type ConvFn = extern "C" fn(
+    num_msg: c_int,
+    msg: *mut *const PamMessage,
+    resp: *mut *mut PamResponse,
+    appdata_ptr: *mut c_void,
+) -> c_int;
+
+#[repr(C)]
+struct PamConv {
+    conv: ConvFn,
+    appdata_ptr: *mut c_void,
+}
+
+type PamStartFn = unsafe extern "C" fn(
+    service_name: *const c_char,
+    user: *const c_char,
+    pam_conversation: *const PamConv,
+    pamh: *mut *mut c_void,
+) -> c_int;
+type PamAuthenticateFn = unsafe extern "C" fn(pamh: *mut c_void, flags: c_int) -> c_int;
+type PamAcctMgmtFn = unsafe extern "C" fn(pamh: *mut c_void, flags: c_int) -> c_int;
+type PamEndFn = unsafe extern "C" fn(pamh: *mut c_void, pam_status: c_int) -> c_int;
+type PamStrerrorFn = unsafe extern "C" fn(pamh: *mut c_void, errnum: c_int) -> *const c_char;
+
+#[derive(Debug)]
+pub enum PamError {
+    Load(libloading::Error),
+    NoUser,
+    // The password was right but the account may not log in
+    Account { code: c_int, message: String },
+    Pam { code: c_int, message: String },
+}
+
+impl fmt::Display for PamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PamError::Load(e) => write!(f, "could not load {}: {}", LIBPAM, e),
+            PamError::NoUser => write!(f, "could not determine the current user"),
+            PamError::Account { code, message } => {
+                write!(f, "account refused ({}): {}", code, message)
+            }
+            PamError::Pam { code, message } => write!(f, "pam error {}: {}", code, message),
+        }
+    }
+}
+
+impl std::error::Error for PamError {}
+
+// Answers passed to the conversation function through `appdata_ptr`
+struct Credentials<'a> {
+    user: CString,
+    password: &'a Secret,
+}
+
+/// Answers PAM prompts: hidden prompts get the password, visible ones the
+/// user name, and informational messages are acknowledged with no reply.
+fn answer<'a>(style: c_int, creds: &'a Credentials) -> Option<Option<&'a CStr>> {
+    match style {
+        PAM_PROMPT_ECHO_OFF => Some(Some(creds.password.as_c_str())),
+        PAM_PROMPT_ECHO_ON => Some(Some(&creds.user)),
+        PAM_ERROR_MSG | PAM_TEXT_INFO => Some(None),
+        _ => None,
+    }
+}
+
+extern "C" fn conversation(
+    num_msg: c_int,
+    msg: *mut *const PamMessage,
+    resp: *mut *mut PamResponse,
+    appdata_ptr: *mut c_void,
+) -> c_int {
+    if num_msg <= 0 || msg.is_null() || resp.is_null() || appdata_ptr.is_null() {
+        return PAM_CONV_ERR;
+    }
+    let creds = unsafe { &*(appdata_ptr as *const Credentials) };
+
+    // PAM takes ownership of the responses and releases them with free()
+    let responses = unsafe {
+        libc::calloc(num_msg as usize, std::mem::size_of::<PamResponse>()) as *mut PamResponse
+    };
+    if responses.is_null() {
+        return PAM_BUF_ERR;
+    }
+
+    for i in 0..num_msg as usize {
+        let style = unsafe { (**msg.add(i)).msg_style };
+        let reply = match answer(style, creds) {
+            Some(reply) => reply,
+            None => {
+                free_responses(responses, i);
+                return PAM_CONV_ERR;
+            }
+        };
+
+        if let Some(reply) = reply {
+            let copy = unsafe { libc::strdup(reply.as_ptr()) };
+            if copy.is_null() {
+                free_responses(responses, i);
+                return PAM_BUF_ERR;
+            }
+            unsafe { (*responses.add(i)).resp = copy };
+        }
+    }
+
+    unsafe { *resp = responses };
+    PAM_SUCCESS
+}
+
+fn free_responses(responses: *mut PamResponse, count: usize) {
+    for i in 0..count {
+        unsafe {
+            let reply = (*responses.add(i)).resp;
+            if !reply.is_null() {
+                libc::explicit_bzero(reply as *mut c_void, libc::strlen(reply));
+                libc::free(reply as *mut c_void);
+            }
+        }
+    }
+    unsafe { libc::free(responses as *mut c_void) };
+}
+
+/// Runs a PAM conversation for the current user against `service`
+/// (a file in /etc/pam.d), then checks the account is still allowed in.
+/// Returns `Ok(false)` for a wrong password and `Err` when PAM itself could
+/// not be used or the account is expired or locked.
+pub fn authenticate(service: &str, password: &Secret) -> Result<bool, PamError> {
+    let user = crate::auth::current_user().ok_or(PamError::NoUser)?;
+    let creds = Credentials {
+        user: CString::new(user).map_err(|_| PamError::NoUser)?,
+        password,
+    };
+    let service = CString::new(service).unwrap_or_default();
+
+    let lib = unsafe { Library::new(LIBPAM) }.map_err(PamError::Load)?;
+    let (pam_start, pam_authenticate, pam_acct_mgmt, pam_end, pam_strerror) = unsafe {
+        (
+            *lib.get::<PamStartFn>(b"pam_start\0")
+                .map_err(PamError::Load)?,
+            *lib.get::<PamAuthenticateFn>(b"pam_authenticate\0")
+                .map_err(PamError::Load)?,
+            *lib.get::<PamAcctMgmtFn>(b"pam_acct_mgmt\0")
+                .map_err(PamError::Load)?,
+            *lib.get::<PamEndFn>(b"pam_end\0").map_err(PamError::Load)?,
+            *lib.get::<PamStrerrorFn>(b"pam_strerror\0")
+                .map_err(PamError::Load)?,
+        )
+    };
+
+    let conv = PamConv {
+        conv: conversation,
+        appdata_ptr: &creds as *const Credentials as *mut c_void,
+    };
+    let mut handle: *mut c_void = ptr::null_mut();
+
+    let status = unsafe { pam_start(service.as_ptr(), creds.user.as_ptr(), &conv, &mut handle) };
+    if status != PAM_SUCCESS {
+        return Err(PamError::Pam {
+            code: status,
+            message: format!("pam_start failed for service {:?}", service),
+        });
+    }
+
+    let strerror = |status| unsafe {
+        let text = pam_strerror(handle, status);
+        if text.is_null() {
+            String::new()
+        } else {
+            CStr::from_ptr(text).to_string_lossy().into_owned()
+        }
+    };
+
+    let status = unsafe { pam_authenticate(handle, 0) };
+    let result = match verdict(status, strerror(status)) {
+        Ok(true) => {
+            let status = unsafe { pam_acct_mgmt(handle, 0) };
+            account_verdict(status, strerror(status))
+        }
+        other => other,
+    };
+    unsafe { pam_end(handle, status) };
+
+    result
+}
+
+// Which pam_authenticate results mean a wrong password rather than PAM
+// failing to check it
+fn verdict(status: c_int, message: String) -> Result<bool, PamError> {
+    match status {
+        PAM_SUCCESS => Ok(true),
+        PAM_AUTH_ERR | PAM_CRED_INSUFFICIENT | PAM_USER_UNKNOWN | PAM_MAXTRIES => Ok(false),
+        code => Err(PamError::Pam { code, message }),
+    }
+}
+
+// pam_acct_mgmt results. An expired password still unlocks: it was typed
+// correctly and there's no way to change it from the lock screen.
+fn account_verdict(status: c_int, message: String) -> Result<bool, PamError> {
+    match status {
+        PAM_SUCCESS | PAM_NEW_AUTHTOK_REQD => Ok(true),
+        PAM_ACCT_EXPIRED | PAM_PERM_DENIED | PAM_AUTH_ERR | PAM_USER_UNKNOWN => {
+            Err(PamError::Account {
+                code: status,
+                message,
+            })
+        }
+        code => Err(PamError::Pam { code, message }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Runs `conversation` the way libpam would, returning its status and
+    // the replies it gave
+    fn converse(styles: &[c_int], creds: &Credentials) -> (c_int, Vec<Option<String>>) {
+        let text = CString::new("prompt").unwrap();
+        let messages: Vec<PamMessage> = styles
+            .iter()
+            .map(|&msg_style| PamMessage {
+                msg_style,
+                msg: text.as_ptr(),
+            })
+            .collect();
+        let mut pointers: Vec<*const PamMessage> = messages.iter().map(|m| m as *const _).collect();
+        let mut responses: *mut PamResponse = ptr::null_mut();
+        let status = conversation(
+            styles.len() as c_int,
+            pointers.as_mut_ptr(),
+            &mut responses,
+            creds as *const Credentials as *mut c_void,
+        );
+        if responses.is_null() {
+            return (status, Vec::new());
+        }
+        let replies = (0..styles.len())
+            .map(|i| unsafe {
+                let reply = (*responses.add(i)).resp;
+                (!reply.is_null()).then(|| CStr::from_ptr(reply).to_string_lossy().into_owned())
+            })
+            .collect();
+        free_responses(responses, styles.len());
+        (status, replies)
+    }
+
+    #[test]
+    fn hidden_prompts_get_the_password() {
+        let password = Secret::from("hunter2");
+        let creds = Credentials {
+            user: CString::new("alice").unwrap(),
+            password: &password,
+        };
+        let (status, replies) = converse(&[PAM_PROMPT_ECHO_OFF], &creds);
+        assert_eq!(status, PAM_SUCCESS);
+        assert_eq!(replies, [Some("hunter2".to_string())]);
+    }
+
+    #[test]
+    fn other_messages_are_answered() {
+        let password = Secret::from("hunter2");
+        let creds = Credentials {
+            user: CString::new("alice").unwrap(),
+            password: &password,
+        };
+        let (status, replies) = converse(
+            &[
+                PAM_TEXT_INFO,
+                PAM_PROMPT_ECHO_ON,
+                PAM_ERROR_MSG,
+                PAM_PROMPT_ECHO_OFF,
+            ],
+            &creds,
+        );
+        assert_eq!(status, PAM_SUCCESS);
+        assert_eq!(
+            replies,
+            [
+                None,
+                Some("alice".to_string()),
+                None,
+                Some("hunter2".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_messages_end_the_conversation() {
+        let password = Secret::from("hunter2");
+        let creds = Credentials {
+            user: CString::new("alice").unwrap(),
+            password: &password,
+        };
+        let (status, replies) = converse(&[PAM_PROMPT_ECHO_OFF, 42], &creds);
+        assert_eq!(status, PAM_CONV_ERR);
+        assert!(replies.is_empty());
+
+        let (status, _) = converse(&[], &creds);
+        assert_eq!(status, PAM_CONV_ERR);
+    }
+
+    #[test]
+    fn statuses_map_to_verdicts() {
+        assert!(matches!(verdict(PAM_SUCCESS, String::new()), Ok(true)));
+        for status in [
+            PAM_AUTH_ERR,
+            PAM_CRED_INSUFFICIENT,
+            PAM_USER_UNKNOWN,
+            PAM_MAXTRIES,
+        ] {
+            assert!(matches!(verdict(status, String::new()), Ok(false)));
+        }
+        // PAM_SYSTEM_ERR, a broken stack rather than a wrong password
+        assert!(matches!(
+            verdict(4, "System error".to_string()),
+            Err(PamError::Pam { code: 4, .. })
+        ));
+    }
+
+    #[test]
+    fn refused_accounts_do_not_unlock() {
+        for status in [PAM_SUCCESS, PAM_NEW_AUTHTOK_REQD] {
+            assert!(matches!(account_verdict(status, String::new()), Ok(true)));
+        }
+        for status in [PAM_ACCT_EXPIRED, PAM_PERM_DENIED, PAM_AUTH_ERR] {
+            assert!(matches!(
+                account_verdict(status, String::new()),
+                Err(PamError::Account { .. })
+            ));
+        }
+        assert!(matches!(
+            account_verdict(4, "System error".to_string()),
+            Err(PamError::Pam { code: 4, .. })
+        ));
+    }
+}
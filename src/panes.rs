@@ -1,28 +1,31 @@
-use egui::ecolor::linear_u8_from_linear_f32;
-use egui::emath::Rot2;
 use egui::epaint::TextShape;
-use egui::{Color32, FontId, Galley, Image, Painter, Pos2, Rect, Rounding};
+use egui::{Painter, Pos2, Rect};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::borrow::BorrowMut;
-use std::collections::HashMap;
 use std::default::Default;
 use std::f32::consts::PI;
+use std::fmt;
+use std::path::PathBuf;
 
 use crate::cpugraph::CpuGraph;
 use crate::diskgraph::DiskGraph;
 use crate::infopane::InfoPane;
 use crate::memgraph::MemGraph;
 use crate::netgraph::NetGraph;
+use crate::settings::find_config_file;
 use crate::table::{ProcessTable, BAR_HEIGHT, ROW_HEIGHT};
+use crate::theme::Theme;
+use crate::ui;
 use crate::ui::get_corners;
-use crate::{configs::*, ui};
 
 // use crate::{graph::CpuGraph, ui};
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum CornerTypes {
-    SQUARE,
+    #[serde(rename = "SQUARE")]
+    Square,
     Ang45,
     Ang30,
     Ang60,
@@ -30,13 +33,13 @@ pub enum CornerTypes {
 
 #[derive(Clone)]
 pub enum TitleFormats {
-    TOP { text: Option<String> },
-    SIDE { text: Option<String> },
+    Top { text: Option<String> },
+    Side { text: Option<String> },
 }
 
 impl Default for TitleFormats {
     fn default() -> Self {
-        TitleFormats::TOP { text: None }
+        TitleFormats::Top { text: None }
     }
 }
 
@@ -107,10 +110,20 @@ impl PaneData {
                 PaneType::ProcTable => PaneData::ProcTable {
                     proc_table: ProcessTable::new(30, 50, 0),
                 },
-                PaneType::No {} => PaneData::No {},
+                PaneType::No => PaneData::No {},
             },
         }
     }
+
+    fn animating(&self) -> bool {
+        match self {
+            PaneData::CpuGraph { cpu_graph } => cpu_graph.animating(),
+            PaneData::MemGraph { mem_graph } => mem_graph.animating(),
+            PaneData::NetGraph { net_graph } => net_graph.animating(),
+            PaneData::DiskGraph { disk_graph } => disk_graph.animating(),
+            _ => false,
+        }
+    }
 }
 
 // Main pane structure that represents either a split or leaf node
@@ -164,20 +177,12 @@ impl Default for Pane {
         }
     }
 }
-impl Default for PaneInstance {
-    fn default() -> Self {
-        PaneInstance {
-            config: Pane::default(),
-            runtime_data: PaneData::default(),
-        }
-    }
-}
 
 // impl Pane {
 //     fn
 // }
 // Runtime instance of a pane that includes both config and runtime data
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, Default)]
 pub struct PaneInstance {
     config: Pane,
     #[serde(skip)]
@@ -189,11 +194,19 @@ impl PaneInstance {
     fn from_config(config: Pane) -> Self {
         PaneInstance {
             runtime_data: PaneData::new(&config),
-            config: config,
+            config,
         }
     }
 
-    pub fn precalc(&mut self, rect: Rect) {
+    // Whether any pane needs frames back to back to finish an animation
+    pub fn animating(&self) -> bool {
+        match &self.config {
+            Pane::Split { first, second, .. } => first.animating() || second.animating(),
+            Pane::Leaf { .. } => self.runtime_data.animating(),
+        }
+    }
+
+    pub fn precalc(&mut self, rect: Rect, theme: &Theme) {
         match &mut self.config {
             Pane::Split {
                 direction,
@@ -203,21 +216,21 @@ impl PaneInstance {
                 ..
             } => {
                 let (first_rect, second_rect) = if *direction == SplitType::V {
-                    let split_x = rect.min.x + rect.width() * bias.clone();
+                    let split_x = rect.min.x + rect.width() * *bias;
                     (
                         Rect::from_min_max(rect.min, egui::pos2(split_x, rect.max.y)),
                         Rect::from_min_max(egui::pos2(split_x, rect.min.y), rect.max),
                     )
                 } else {
-                    let mut split_y = rect.min.y + rect.height() * bias.clone();
+                    let split_y = rect.min.y + rect.height() * *bias;
                     (
                         Rect::from_min_max(rect.min, egui::pos2(rect.max.x, split_y)),
                         Rect::from_min_max(egui::pos2(rect.min.x, split_y), rect.max),
                     )
                 };
 
-                first.precalc(first_rect);
-                second.precalc(second_rect);
+                first.precalc(first_rect, theme);
+                second.precalc(second_rect, theme);
             }
             Pane::Leaf {
                 pane_type,
@@ -225,9 +238,9 @@ impl PaneInstance {
                 rect: rect2,
                 inner_rect,
                 container_points,
-                title_type: title_type,
+                title_type,
             } => {
-                container_points.clone_from(&get_corners(rect, corners.to_owned()));
+                container_points.clone_from(&get_corners(rect, corners.to_owned(), theme));
                 rect2.clone_from(&rect);
                 inner_rect.clone_from(&ui::find_largest_rectangle(container_points).unwrap());
 
@@ -248,20 +261,17 @@ impl PaneInstance {
                     + (rect2.right() - inner_rect.right()))
                     > ((inner_rect.top() - rect2.top()) + (rect2.bottom() - inner_rect.bottom()))
                 {
-                    TitleFormats::SIDE { text: text }
+                    TitleFormats::Side { text }
                 } else {
-                    TitleFormats::TOP { text: text }
+                    TitleFormats::Top { text }
                 });
 
-                match pane_type {
-                    PaneType::ProcTable { .. } => {
-                        if let PaneData::ProcTable { proc_table } = self.runtime_data.borrow_mut() {
-                            proc_table.row_count =
-                                ((inner_rect.height() - BAR_HEIGHT) / ROW_HEIGHT).floor() as usize;
-                            // cpu_proc_table.row_count = 10;
-                        }
+                if let PaneType::ProcTable = pane_type {
+                    if let PaneData::ProcTable { proc_table } = self.runtime_data.borrow_mut() {
+                        proc_table.row_count =
+                            ((inner_rect.height() - BAR_HEIGHT) / ROW_HEIGHT).floor() as usize;
+                        // cpu_proc_table.row_count = 10;
                     }
-                    _ => {}
                 }
                 // let adj_rect = background_render(painter, rect, *corners);
             }
@@ -269,11 +279,11 @@ impl PaneInstance {
     }
 
     // Render the pane and its children
-    pub fn render(&mut self, painter: &Painter) {
+    pub fn render(&mut self, painter: &Painter, theme: &Theme) {
         match &mut self.config {
             Pane::Split { first, second, .. } => {
-                first.render(painter);
-                second.render(painter);
+                first.render(painter, theme);
+                second.render(painter, theme);
             }
             Pane::Leaf {
                 pane_type,
@@ -285,34 +295,34 @@ impl PaneInstance {
             } => {
                 painter.add(egui::Shape::convex_polygon(
                     container_points.to_owned(),
-                    BACKGROUND_2,
-                    egui::Stroke::new(0.5, TEXT_COLOR),
+                    theme.pane_background,
+                    egui::Stroke::new(theme.pane_outline_width, theme.text_color),
                 ));
 
                 match title_type {
-                    TitleFormats::SIDE { text } => {
+                    TitleFormats::Side { text } => {
                         let galley = painter.layout_no_wrap(
                             text.as_ref().unwrap().to_owned(),
-                            TITLE_FONT,
-                            TEXT_COLOR,
+                            theme.title_font(),
+                            theme.text_color,
                         );
                         let size = galley.size();
                         let mid_x = rect.min.x + (f32::abs(rect.min.x - inner_rect.min.x) / 2.)
                             - (size.y / 2.)
-                            + PANE_GAP;
+                            + theme.pane_gap;
                         let mid_y = (inner_rect.min.y
                             + f32::abs(inner_rect.min.y - inner_rect.max.y) / 2.)
                             + size.x / 2.;
                         painter.add(
-                            TextShape::new(Pos2 { x: mid_x, y: mid_y }, galley, Color32::WHITE)
+                            TextShape::new(Pos2 { x: mid_x, y: mid_y }, galley, theme.text_color)
                                 .with_angle(-PI / 2.),
                         );
                     }
-                    TitleFormats::TOP { text } => {
+                    TitleFormats::Top { text } => {
                         let galley = painter.layout_no_wrap(
                             text.as_ref().unwrap().to_owned(),
-                            TITLE_FONT,
-                            TEXT_COLOR,
+                            theme.title_font(),
+                            theme.text_color,
                         );
                         let size = galley.size();
                         let mid_x = rect.min.x
@@ -320,11 +330,11 @@ impl PaneInstance {
                             - (size.x / 2.);
                         let mid_y = (rect.min.y + f32::abs(rect.min.y - inner_rect.min.y) / 2.)
                             - (size.y / 2.)
-                            + PANE_GAP;
+                            + theme.pane_gap;
                         painter.add(TextShape::new(
                             Pos2 { x: mid_x, y: mid_y },
                             galley,
-                            Color32::WHITE,
+                            theme.text_color,
                         ));
                     }
                 }
@@ -332,27 +342,57 @@ impl PaneInstance {
                 painter.rect_stroke(
                     inner_rect.to_owned(),
                     0.0,
-                    egui::Stroke::new(0.25, TEXT_COLOR),
+                    egui::Stroke::new(theme.inner_outline_width, theme.text_color),
                 );
 
                 match pane_type {
                     PaneType::Info => {
-                        render_info(painter, inner_rect.to_owned(), &mut self.runtime_data);
+                        render_info(
+                            painter,
+                            inner_rect.to_owned(),
+                            &mut self.runtime_data,
+                            theme,
+                        );
                     }
                     PaneType::CpuGraph => {
-                        render_cpu_graph(painter, inner_rect.to_owned(), &mut self.runtime_data);
+                        render_cpu_graph(
+                            painter,
+                            inner_rect.to_owned(),
+                            &mut self.runtime_data,
+                            theme,
+                        );
                     }
                     PaneType::MemGraph => {
-                        render_mem_graph(painter, inner_rect.to_owned(), &mut self.runtime_data);
+                        render_mem_graph(
+                            painter,
+                            inner_rect.to_owned(),
+                            &mut self.runtime_data,
+                            theme,
+                        );
                     }
                     PaneType::NetGraph => {
-                        render_net_graph(painter, inner_rect.to_owned(), &mut self.runtime_data);
+                        render_net_graph(
+                            painter,
+                            inner_rect.to_owned(),
+                            &mut self.runtime_data,
+                            theme,
+                        );
                     }
                     PaneType::DiskGraph => {
-                        render_disk_graph(painter, inner_rect.to_owned(), &mut self.runtime_data);
+                        render_disk_graph(
+                            painter,
+                            inner_rect.to_owned(),
+                            &mut self.runtime_data,
+                            theme,
+                        );
                     }
                     PaneType::ProcTable => {
-                        render_proc_table(painter, inner_rect.to_owned(), &mut self.runtime_data);
+                        render_proc_table(
+                            painter,
+                            inner_rect.to_owned(),
+                            &mut self.runtime_data,
+                            theme,
+                        );
                     }
                     PaneType::No => {}
                 }
@@ -361,57 +401,178 @@ impl PaneInstance {
     }
 }
 
-pub fn render_info(painter: &Painter, rect: Rect, data: &mut PaneData) {
+pub fn render_info(painter: &Painter, rect: Rect, data: &mut PaneData, theme: &Theme) {
     if let PaneData::Info { info_man } = data {
         info_man.update();
-        info_man.render(painter, rect);
+        info_man.render(painter, rect, theme);
     }
 }
 
 // Example rendering functions for different pane types
-pub fn render_cpu_graph(painter: &Painter, rect: Rect, data: &mut PaneData) {
+pub fn render_cpu_graph(painter: &Painter, rect: Rect, data: &mut PaneData, theme: &Theme) {
     if let PaneData::CpuGraph { cpu_graph } = data {
         cpu_graph.update();
-        cpu_graph.render(painter, rect);
+        cpu_graph.render(painter, rect, &theme.graph);
     }
 }
 
-pub fn render_mem_graph(painter: &Painter, rect: Rect, data: &mut PaneData) {
+pub fn render_mem_graph(painter: &Painter, rect: Rect, data: &mut PaneData, theme: &Theme) {
     if let PaneData::MemGraph { mem_graph } = data {
         mem_graph.update();
-        mem_graph.render(painter, rect);
+        mem_graph.render(painter, rect, &theme.graph);
     }
 }
 
-pub fn render_net_graph(painter: &Painter, rect: Rect, data: &mut PaneData) {
+pub fn render_net_graph(painter: &Painter, rect: Rect, data: &mut PaneData, theme: &Theme) {
     if let PaneData::NetGraph { net_graph } = data {
         net_graph.update();
-        net_graph.render(painter, rect);
+        net_graph.render(painter, rect, &theme.graph);
     }
 }
 
-pub fn render_disk_graph(painter: &Painter, rect: Rect, data: &mut PaneData) {
+pub fn render_disk_graph(painter: &Painter, rect: Rect, data: &mut PaneData, theme: &Theme) {
     if let PaneData::DiskGraph { disk_graph } = data {
         disk_graph.update();
-        disk_graph.render(painter, rect);
+        disk_graph.render(painter, rect, &theme.graph);
     }
 }
 
-pub fn render_proc_table(painter: &Painter, rect: Rect, data: &mut PaneData) {
+pub fn render_proc_table(painter: &Painter, rect: Rect, data: &mut PaneData, theme: &Theme) {
     if let PaneData::ProcTable { proc_table } = data {
         proc_table.update();
-        proc_table.render(painter, rect);
+        proc_table.render(painter, rect, &theme.table);
     }
 }
 
+/// The pane tree drawn on one output or window
+pub struct Screen {
+    root_pane: PaneInstance,
+    fit: Option<(Rect, f32)>,
+}
+
+impl Screen {
+    pub fn new(root_pane: PaneInstance) -> Self {
+        Screen {
+            root_pane,
+            fit: None,
+        }
+    }
+
+    // precalc is expensive, only redo it when the size or scale changed
+    pub fn fit(&mut self, rect: Rect, pixels_per_point: f32, theme: &Theme) {
+        let fit = (rect, pixels_per_point);
+        if self.fit != Some(fit) {
+            self.root_pane.precalc(rect, theme);
+            self.fit = Some(fit);
+        }
+    }
+
+    pub fn animating(&self) -> bool {
+        self.root_pane.animating()
+    }
+
+    pub fn root_pane(&mut self) -> &mut PaneInstance {
+        &mut self.root_pane
+    }
+}
+
+const LAYOUT_FILENAME: &str = "layout.json";
+
+#[derive(Debug)]
+pub enum LayoutError {
+    Io(std::io::Error),
+    Syntax(serde_json::Error),
+    // `path` is a JSON path like `$.a.b.pane_type`
+    Invalid { path: String, message: String },
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LayoutError::Io(e) => write!(f, "{}", e),
+            LayoutError::Syntax(e) => write!(f, "{}", e),
+            LayoutError::Invalid { path, message } => write!(f, "{}: {}", path, message),
+        }
+    }
+}
+
+impl std::error::Error for LayoutError {}
+
+/// Loads `path`, or the first `layout.json` in the config directories.
+/// Falls back to the built-in layout when there is none or it's broken.
+pub fn load_layout(path: Option<PathBuf>) -> PaneInstance {
+    if let Some(path) = path.or_else(|| find_config_file(LAYOUT_FILENAME)) {
+        let layout = std::fs::read_to_string(&path)
+            .map_err(LayoutError::Io)
+            .and_then(|json| load_pane_config(&json));
+        match layout {
+            Ok(layout) => return layout,
+            Err(e) => eprintln!(
+                "raylock: {}: {}, using the default layout",
+                path.display(),
+                e
+            ),
+        }
+    }
+    load_pane_config(EXAMPLE_CONFIG).expect("the built-in layout is valid")
+}
+
 // Function to load pane configuration from JSON
-pub fn load_pane_config(json: &str) -> Result<PaneInstance, serde_json::Error> {
-    let config: Pane = serde_json::from_str(json)?;
+pub fn load_pane_config(json: &str) -> Result<PaneInstance, LayoutError> {
+    let value: Value = serde_json::from_str(json).map_err(LayoutError::Syntax)?;
+    let config = parse_pane(&value, "$")?;
 
     // Create the pane hierarchy with runtime data
     Ok(create_pane_instance(config))
 }
 
+// Walks the tree by hand so errors can say which node is wrong, serde's
+// internally tagged enums lose track of where they are
+fn parse_pane(value: &Value, path: &str) -> Result<Pane, LayoutError> {
+    match field::<String>(value, "kind", path)?.as_str() {
+        "Split" => Ok(Pane::Split {
+            direction: field(value, "direction", path)?,
+            bias: field(value, "bias", path)?,
+            first: Box::default(),
+            second: Box::default(),
+            a: Box::new(parse_pane(
+                child(value, "a", path)?,
+                &format!("{}.a", path),
+            )?),
+            b: Box::new(parse_pane(
+                child(value, "b", path)?,
+                &format!("{}.b", path),
+            )?),
+        }),
+        "Leaf" => Ok(Pane::Leaf {
+            pane_type: field(value, "pane_type", path)?,
+            corners: field(value, "corners", path)?,
+            rect: DEFAULT_RECT,
+            inner_rect: DEFAULT_RECT,
+            container_points: DEFAULT_POINTS,
+            title_type: TitleFormats::default(),
+        }),
+        kind => Err(LayoutError::Invalid {
+            path: format!("{}.kind", path),
+            message: format!("unknown kind '{}', expected Leaf or Split", kind),
+        }),
+    }
+}
+
+fn child<'a>(value: &'a Value, name: &str, path: &str) -> Result<&'a Value, LayoutError> {
+    value.get(name).ok_or_else(|| LayoutError::Invalid {
+        path: path.to_string(),
+        message: format!("missing field '{}'", name),
+    })
+}
+
+fn field<T: DeserializeOwned>(value: &Value, name: &str, path: &str) -> Result<T, LayoutError> {
+    T::deserialize(child(value, name, path)?).map_err(|e| LayoutError::Invalid {
+        path: format!("{}.{}", path, name),
+        message: e.to_string(),
+    })
+}
+
 // Helper function to create the pane hierarchy
 pub fn create_pane_instance(config: Pane) -> PaneInstance {
     match config {
@@ -499,3 +660,48 @@ pub const EXAMPLE_CONFIG: &str = r#"
     }
 }
 "#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaf(pane_type: &str) -> String {
+        format!(
+            r#"{{"kind": "Leaf", "corners": ["SQUARE", "SQUARE", "SQUARE", "SQUARE"], "pane_type": "{}"}}"#,
+            pane_type
+        )
+    }
+
+    fn split(a: &str, b: &str) -> String {
+        format!(
+            r#"{{"kind": "Split", "direction": "H", "bias": 0.5, "a": {}, "b": {}}}"#,
+            a, b
+        )
+    }
+
+    #[test]
+    fn errors_name_the_broken_node() {
+        let layout = split(&leaf("Info"), &split(&leaf("Clock"), &leaf("CpuGraph")));
+        match load_pane_config(&layout) {
+            Err(LayoutError::Invalid { path, message }) => {
+                assert_eq!(path, "$.b.a.pane_type");
+                assert!(message.contains("Clock"), "{}", message);
+            }
+            _ => panic!("a bad pane_type should be rejected"),
+        }
+    }
+
+    #[test]
+    fn a_broken_layout_falls_back_to_the_default() {
+        let path = std::env::temp_dir().join(format!("raylock-layout-{}.json", std::process::id()));
+        std::fs::write(&path, split(&leaf("Info"), r#"{"kind": "Leaf"}"#)).unwrap();
+        let layout = load_layout(Some(path.clone()));
+        std::fs::remove_file(&path).unwrap();
+
+        let default = load_pane_config(EXAMPLE_CONFIG).unwrap();
+        assert_eq!(
+            serde_json::to_value(&layout).unwrap(),
+            serde_json::to_value(&default).unwrap()
+        );
+    }
+}
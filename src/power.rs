@@ -0,0 +1,371 @@
+use eframe::egui::{Key, Modifiers};
+use serde::Deserialize;
+use std::fmt;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use zbus::blocking::Connection;
+
+const LOGIND_SERVICE: &str = "org.freedesktop.login1";
+const LOGIND_PATH: &str = "/org/freedesktop/login1";
+const LOGIND_MANAGER: &str = "org.freedesktop.login1.Manager";
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum PowerAction {
+    Suspend,
+    Hibernate,
+    Reboot,
+    PowerOff,
+}
+
+impl PowerAction {
+    /// In menu order, F1 to F4
+    pub const ALL: [PowerAction; 4] = [
+        PowerAction::Suspend,
+        PowerAction::Hibernate,
+        PowerAction::Reboot,
+        PowerAction::PowerOff,
+    ];
+
+    /// The function key that picks this action, from the menu or straight
+    /// from the password prompt
+    pub fn from_key(key: Key) -> Option<PowerAction> {
+        match key {
+            Key::F1 => Some(PowerAction::Suspend),
+            Key::F2 => Some(PowerAction::Hibernate),
+            Key::F3 => Some(PowerAction::Reboot),
+            Key::F4 => Some(PowerAction::PowerOff),
+            _ => None,
+        }
+    }
+
+    fn key_name(&self) -> &'static str {
+        match self {
+            PowerAction::Suspend => "F1",
+            PowerAction::Hibernate => "F2",
+            PowerAction::Reboot => "F3",
+            PowerAction::PowerOff => "F4",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            PowerAction::Suspend => "Suspend",
+            PowerAction::Hibernate => "Hibernate",
+            PowerAction::Reboot => "Reboot",
+            PowerAction::PowerOff => "Power off",
+        }
+    }
+
+    // Method on logind's Manager interface
+    fn method(&self) -> &'static str {
+        match self {
+            PowerAction::Suspend => "Suspend",
+            PowerAction::Hibernate => "Hibernate",
+            PowerAction::Reboot => "Reboot",
+            PowerAction::PowerOff => "PowerOff",
+        }
+    }
+}
+
+/// Which actions the lock screen offers, only suspend unless configured.
+/// logind and polkit still have the final say.
+#[derive(Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PowerSettings {
+    pub suspend: bool,
+    pub hibernate: bool,
+    pub reboot: bool,
+    pub power_off: bool,
+}
+
+impl Default for PowerSettings {
+    fn default() -> Self {
+        PowerSettings {
+            suspend: true,
+            hibernate: false,
+            reboot: false,
+            power_off: false,
+        }
+    }
+}
+
+impl PowerSettings {
+    pub fn allows(&self, action: PowerAction) -> bool {
+        match action {
+            PowerAction::Suspend => self.suspend,
+            PowerAction::Hibernate => self.hibernate,
+            PowerAction::Reboot => self.reboot,
+            PowerAction::PowerOff => self.power_off,
+        }
+    }
+
+    /// The action a key press picks, if it's a bare function key for an
+    /// action that is turned on
+    pub fn pick(&self, key: Key, modifiers: Modifiers) -> Option<PowerAction> {
+        PowerAction::from_key(key).filter(|action| modifiers.is_none() && self.allows(*action))
+    }
+}
+
+#[derive(Debug)]
+pub enum PowerError {
+    Bus(zbus::Error),
+}
+
+impl fmt::Display for PowerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PowerError::Bus(zbus::Error::MethodError(name, Some(message), _)) => {
+                write!(f, "{}: {}", name, message)
+            }
+            PowerError::Bus(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for PowerError {}
+
+impl From<zbus::Error> for PowerError {
+    fn from(e: zbus::Error) -> Self {
+        PowerError::Bus(e)
+    }
+}
+
+/// Asks logind on `connection`, the system bus outside tests, to carry out
+/// `action`. Not interactive, there is no one to answer a polkit prompt
+/// behind a lock screen.
+pub fn request(connection: &Connection, action: PowerAction) -> Result<(), PowerError> {
+    connection.call_method(
+        Some(LOGIND_SERVICE),
+        LOGIND_PATH,
+        Some(LOGIND_MANAGER),
+        action.method(),
+        &(false,),
+    )?;
+    Ok(())
+}
+
+/// The power overlay, from picking an action to logind's answer
+pub enum Menu {
+    Choosing,
+    Confirming(PowerAction),
+    // Sent to logind on its own thread, the bus may be slow
+    Requested(PowerAction, Receiver<Result<(), PowerError>>),
+    Failed(PowerAction, String),
+}
+
+impl Menu {
+    /// Sends `action` to logind on the bus `connect` opens, usually
+    /// `Connection::system`
+    pub fn confirm<F>(action: PowerAction, connect: F) -> Menu
+    where
+        F: FnOnce() -> zbus::Result<Connection> + Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let result = connect()
+                .map_err(PowerError::from)
+                .and_then(|connection| request(&connection, action));
+            let _ = tx.send(result);
+        });
+        Menu::Requested(action, rx)
+    }
+
+    /// Picks up logind's answer. `None` means the menu is done with and
+    /// should close.
+    pub fn poll(self) -> Option<Menu> {
+        let Menu::Requested(action, rx) = self else {
+            return Some(self);
+        };
+        match rx.try_recv() {
+            Ok(Ok(())) => None,
+            Ok(Err(e)) => {
+                eprintln!("raylock: {} failed: {}", action.label(), e);
+                Some(Menu::Failed(action, e.to_string()))
+            }
+            Err(TryRecvError::Empty) => Some(Menu::Requested(action, rx)),
+            Err(TryRecvError::Disconnected) => Some(Menu::Failed(
+                action,
+                "the request thread stopped".to_string(),
+            )),
+        }
+    }
+
+    pub fn waiting(&self) -> bool {
+        matches!(self, Menu::Requested(..))
+    }
+
+    /// What the overlay says, one entry per line
+    pub fn lines(&self, settings: &PowerSettings) -> Vec<String> {
+        match self {
+            Menu::Choosing => PowerAction::ALL
+                .into_iter()
+                .filter(|action| settings.allows(*action))
+                .map(|action| format!("{:<4}{}", action.key_name(), action.label()))
+                .chain(["Esc Cancel".to_string()])
+                .collect(),
+            Menu::Confirming(action) => vec![
+                format!("{}?", action.label()),
+                "Enter to confirm, Esc to cancel".to_string(),
+            ],
+            Menu::Requested(action, _) => vec![format!("{}…", action.label())],
+            Menu::Failed(action, error) => vec![
+                format!("{} failed", action.label()),
+                error.clone(),
+                "Esc to close".to_string(),
+            ],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixStream;
+    use std::sync::mpsc::Sender;
+    use std::time::{Duration, Instant};
+    use zbus::blocking::connection::Builder;
+    use zbus::fdo;
+
+    const EVERYTHING: PowerSettings = PowerSettings {
+        suspend: true,
+        hibernate: true,
+        reboot: true,
+        power_off: true,
+    };
+
+    /// logind's Manager, recording which method was called and whether it
+    /// was asked to be interactive
+    struct Logind {
+        calls: Sender<(&'static str, bool)>,
+        // Answers like polkit turning the request down
+        deny: bool,
+    }
+
+    impl Logind {
+        fn call(&self, method: &'static str, interactive: bool) -> fdo::Result<()> {
+            let _ = self.calls.send((method, interactive));
+            if self.deny {
+                return Err(fdo::Error::AccessDenied("Access denied".to_string()));
+            }
+            Ok(())
+        }
+    }
+
+    #[zbus::interface(name = "org.freedesktop.login1.Manager")]
+    impl Logind {
+        fn suspend(&self, interactive: bool) -> fdo::Result<()> {
+            self.call("Suspend", interactive)
+        }
+
+        fn hibernate(&self, interactive: bool) -> fdo::Result<()> {
+            self.call("Hibernate", interactive)
+        }
+
+        fn reboot(&self, interactive: bool) -> fdo::Result<()> {
+            self.call("Reboot", interactive)
+        }
+
+        fn power_off(&self, interactive: bool) -> fdo::Result<()> {
+            self.call("PowerOff", interactive)
+        }
+    }
+
+    /// A connection straight to a fake logind, no bus in between. The
+    /// fake's own end of it comes back too, the fake goes away with it.
+    fn fake_logind(deny: bool) -> (Connection, Connection, Receiver<(&'static str, bool)>) {
+        let (ours, theirs) = UnixStream::pair().unwrap();
+        let (calls, received) = mpsc::channel();
+        let server = thread::spawn(move || {
+            Builder::unix_stream(theirs)
+                .server(zbus::Guid::generate())
+                .unwrap()
+                .p2p()
+                .serve_at(LOGIND_PATH, Logind { calls, deny })
+                .unwrap()
+                .build()
+                .unwrap()
+        });
+        let client = Builder::unix_stream(ours).p2p().build().unwrap();
+        (client, server.join().unwrap(), received)
+    }
+
+    // Polls the menu the way the UI does, until logind answers
+    fn settle(mut menu: Menu) -> Option<Menu> {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while menu.waiting() {
+            assert!(Instant::now() < deadline, "logind never answered");
+            thread::sleep(Duration::from_millis(10));
+            menu = menu.poll()?;
+        }
+        Some(menu)
+    }
+
+    #[test]
+    fn each_entry_calls_its_logind_method() {
+        for (action, method) in [
+            (PowerAction::Suspend, "Suspend"),
+            (PowerAction::Hibernate, "Hibernate"),
+            (PowerAction::Reboot, "Reboot"),
+            (PowerAction::PowerOff, "PowerOff"),
+        ] {
+            let (client, _server, calls) = fake_logind(false);
+            let menu = Menu::confirm(action, move || Ok(client));
+            assert!(settle(menu).is_none(), "{} didn't close the menu", method);
+            assert_eq!(calls.try_recv().unwrap(), (method, false));
+        }
+    }
+
+    #[test]
+    fn refusals_are_shown() {
+        let (client, _server, calls) = fake_logind(true);
+        let menu = Menu::confirm(PowerAction::Reboot, move || Ok(client));
+        let Some(menu @ Menu::Failed(PowerAction::Reboot, _)) = settle(menu) else {
+            panic!("the refusal was not reported");
+        };
+        assert_eq!(calls.try_recv().unwrap(), ("Reboot", false));
+        let lines = menu.lines(&EVERYTHING);
+        assert_eq!(lines[0], "Reboot failed");
+        assert!(lines[1].contains("Access denied"), "{}", lines[1]);
+    }
+
+    #[test]
+    fn unreachable_buses_are_shown() {
+        let menu = Menu::confirm(PowerAction::Suspend, || {
+            Err(zbus::Error::Address("no bus here".to_string()))
+        });
+        assert!(matches!(
+            settle(menu),
+            Some(Menu::Failed(PowerAction::Suspend, _))
+        ));
+    }
+
+    #[test]
+    fn only_suspend_is_offered_by_default() {
+        let settings = PowerSettings::default();
+        assert_eq!(
+            Menu::Choosing.lines(&settings),
+            ["F1  Suspend", "Esc Cancel"]
+        );
+        for key in [Key::F2, Key::F3, Key::F4] {
+            assert!(settings.pick(key, Modifiers::NONE).is_none());
+        }
+    }
+
+    #[test]
+    fn toggled_off_entries_are_hidden_and_not_picked() {
+        let settings = PowerSettings {
+            hibernate: false,
+            power_off: false,
+            ..EVERYTHING
+        };
+        assert_eq!(
+            Menu::Choosing.lines(&settings),
+            ["F1  Suspend", "F3  Reboot", "Esc Cancel"]
+        );
+        assert!(settings.pick(Key::F1, Modifiers::NONE) == Some(PowerAction::Suspend));
+        assert!(settings.pick(Key::F2, Modifiers::NONE).is_none());
+        assert!(settings.pick(Key::F4, Modifiers::NONE).is_none());
+        // Ctrl+F3 is left to the compositor
+        assert!(settings.pick(Key::F3, Modifiers::CTRL).is_none());
+    }
+}
@@ -0,0 +1,65 @@
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::fd::{FromRawFd, OwnedFd, RawFd};
+use std::sync::Mutex;
+
+// Written to once the lock is up, then closed
+static READY: Mutex<Option<OwnedFd>> = Mutex::new(None);
+
+/// Reports readiness on `fd`, which the caller opened for us (--ready-fd)
+pub fn set_fd(fd: RawFd) -> io::Result<()> {
+    if unsafe { libc::fcntl(fd, libc::F_GETFD) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    // Not for the hooks or anything else we start
+    unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) };
+    *READY.lock().unwrap() = Some(unsafe { OwnedFd::from_raw_fd(fd) });
+    Ok(())
+}
+
+/// Forks into the background. The parent only exits once the child is
+/// ready, with 0, or with 1 if the child died first, so whoever started
+/// raylock can wait for the screen to actually be locked.
+///
+/// Must run before any thread is started.
+pub fn daemonize() -> io::Result<()> {
+    let (reader, writer) = pipe()?;
+    match unsafe { libc::fork() } {
+        -1 => Err(io::Error::last_os_error()),
+        0 => {
+            drop(reader);
+            unsafe { libc::setsid() };
+            *READY.lock().unwrap() = Some(writer);
+            Ok(())
+        }
+        _ => {
+            drop(writer);
+            let mut byte = [0u8; 1];
+            let ready = File::from(reader)
+                .read(&mut byte)
+                .is_ok_and(|read| read == 1);
+            std::process::exit(if ready { 0 } else { 1 });
+        }
+    }
+}
+
+/// A pipe with both ends closed on exec
+pub fn pipe() -> io::Result<(OwnedFd, OwnedFd)> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) })
+}
+
+/// Tells whoever is waiting that the lock is up. Only the first call does
+/// anything.
+pub fn signal() {
+    let Some(fd) = READY.lock().unwrap().take() else {
+        return;
+    };
+    // Same as swaylock, a newline and then the descriptor is closed
+    if let Err(e) = File::from(fd).write_all(b"\n") {
+        eprintln!("raylock: could not report readiness: {}", e);
+    }
+}
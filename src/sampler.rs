@@ -0,0 +1,349 @@
+use arc_swap::ArcSwap;
+use serde::{Deserialize, Deserializer};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+use sysinfo::{
+    CpuRefreshKind, Networks, ProcessRefreshKind, ProcessesToUpdate, RefreshKind, System,
+    UpdateKind,
+};
+
+use crate::indicators;
+
+// Longest the thread sleeps, so a metric asked for later starts promptly
+const MAX_SLEEP: Duration = Duration::from_millis(250);
+// Shortest interval a metric can be refreshed at, 0 would spin the thread
+const MIN_INTERVAL_MS: u64 = 50;
+// The lock key LEDs change from the keyboard, not worth a setting
+const LEDS_MS: u64 = 250;
+
+static SAMPLER: OnceLock<Sampler> = OnceLock::new();
+
+/// How often each metric is refreshed, in milliseconds
+#[derive(Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SamplerSettings {
+    #[serde(deserialize_with = "interval")]
+    pub cpu_ms: u64,
+    #[serde(deserialize_with = "interval")]
+    pub memory_ms: u64,
+    #[serde(deserialize_with = "interval")]
+    pub network_ms: u64,
+    #[serde(deserialize_with = "interval")]
+    pub disk_ms: u64,
+    #[serde(deserialize_with = "interval")]
+    pub processes_ms: u64,
+}
+
+fn interval<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    Ok(u64::deserialize(deserializer)?.max(MIN_INTERVAL_MS))
+}
+
+impl Default for SamplerSettings {
+    fn default() -> Self {
+        SamplerSettings {
+            cpu_ms: 666,
+            memory_ms: 666,
+            network_ms: 666,
+            disk_ms: 666,
+            processes_ms: 500,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Metric {
+    Cpu,
+    Memory,
+    Network,
+    Disk,
+    Processes,
+    // Caps Lock and Num Lock, for the window mode's indicators
+    Leds,
+}
+
+const METRICS: [Metric; 6] = [
+    Metric::Cpu,
+    Metric::Memory,
+    Metric::Network,
+    Metric::Disk,
+    Metric::Processes,
+    Metric::Leds,
+];
+
+/// One reading of a metric. `seq` goes up with every reading, a pane
+/// compares it to the last one it saw to tell a new sample from a repeat.
+pub struct Sample<T> {
+    pub seq: u64,
+    pub value: T,
+}
+
+#[derive(Default)]
+pub struct Memory {
+    pub total: u64,
+    pub used: u64,
+    pub available: u64,
+}
+
+// Bytes per second
+#[derive(Default)]
+pub struct Throughput {
+    pub up: f32,
+    pub down: f32,
+}
+
+#[derive(Clone)]
+pub struct Process {
+    pub pid: sysinfo::Pid,
+    pub name: String,
+    pub command: String,
+    pub cpu_usage: f32,
+    pub memory_bytes: u64,
+    pub memory_percent: f32,
+}
+
+/// Latest readings, written by the sampler thread and read by the panes
+/// while they render. Only metrics some pane asked for are sampled.
+pub struct Sampler {
+    pub cpu: ArcSwap<Sample<Vec<f32>>>,
+    pub memory: ArcSwap<Sample<Memory>>,
+    pub network: ArcSwap<Sample<Throughput>>,
+    pub disk: ArcSwap<Sample<Throughput>>,
+    pub processes: ArcSwap<Sample<Vec<Process>>>,
+    // Caps Lock and Num Lock
+    pub leds: ArcSwap<Sample<(bool, bool)>>,
+    wanted: [AtomicBool; 6],
+    // When the next wanted metric is due, so the UI can sleep until then
+    next_due: Mutex<Option<Instant>>,
+}
+
+fn empty<T: Default>() -> ArcSwap<Sample<T>> {
+    ArcSwap::from_pointee(Sample {
+        seq: 0,
+        value: T::default(),
+    })
+}
+
+/// Starts the sampler thread. Only the first call does anything.
+pub fn start(settings: SamplerSettings) {
+    let mut started = false;
+    SAMPLER.get_or_init(|| {
+        started = true;
+        Sampler {
+            cpu: empty(),
+            memory: empty(),
+            network: empty(),
+            disk: empty(),
+            processes: empty(),
+            leds: empty(),
+            wanted: Default::default(),
+            next_due: Mutex::new(None),
+        }
+    });
+    if started {
+        thread::spawn(move || run(SAMPLER.get().unwrap(), settings));
+    }
+}
+
+/// The sampler, started with the default rates if nobody started it yet
+pub fn get() -> &'static Sampler {
+    start(SamplerSettings::default());
+    SAMPLER.get().unwrap()
+}
+
+impl Sampler {
+    /// Asks the thread to start sampling `metric`
+    pub fn want(&self, metric: Metric) {
+        self.wanted[metric as usize].store(true, Ordering::Relaxed);
+    }
+
+    fn wants(&self, metric: Metric) -> bool {
+        self.wanted[metric as usize].load(Ordering::Relaxed)
+    }
+
+    /// When the next sample will be taken, `None` if nothing is wanted
+    pub fn next_due(&self) -> Option<Instant> {
+        *self.next_due.lock().unwrap()
+    }
+}
+
+fn publish<T>(slot: &ArcSwap<Sample<T>>, value: T) {
+    let seq = slot.load().seq + 1;
+    slot.store(Arc::new(Sample { seq, value }));
+}
+
+// Each metric keeps its own System, refreshing one for a metric would
+// otherwise reset the deltas another one measures
+struct Sources {
+    cpu: System,
+    memory: System,
+    network: Networks,
+    disk: System,
+    processes: System,
+    // When the network and disk counters were last read
+    network_read: Instant,
+    disk_read: Instant,
+}
+
+fn run(sampler: &Sampler, settings: SamplerSettings) {
+    let interval = |metric| {
+        Duration::from_millis(match metric {
+            Metric::Cpu => settings.cpu_ms,
+            Metric::Memory => settings.memory_ms,
+            Metric::Network => settings.network_ms,
+            Metric::Disk => settings.disk_ms,
+            Metric::Processes => settings.processes_ms,
+            Metric::Leds => LEDS_MS,
+        })
+    };
+
+    let now = Instant::now();
+    let mut sources = Sources {
+        cpu: System::new_with_specifics(
+            RefreshKind::new().with_cpu(CpuRefreshKind::new().with_cpu_usage()),
+        ),
+        memory: System::new(),
+        network: Networks::new_with_refreshed_list(),
+        disk: System::new_with_specifics(
+            RefreshKind::new().with_processes(ProcessRefreshKind::new().with_disk_usage()),
+        ),
+        processes: System::new(),
+        network_read: now,
+        disk_read: now,
+    };
+    let mut due = [now; 6];
+
+    loop {
+        let now = Instant::now();
+        for metric in METRICS {
+            if sampler.wants(metric) && due[metric as usize] <= now {
+                sample(sampler, &mut sources, metric);
+                due[metric as usize] = now + interval(metric);
+            }
+        }
+
+        let next = METRICS
+            .into_iter()
+            .filter(|metric| sampler.wants(*metric))
+            .map(|metric| due[metric as usize])
+            .min();
+        *sampler.next_due.lock().unwrap() = next;
+        let next = next.unwrap_or(now + MAX_SLEEP);
+        thread::sleep(
+            next.saturating_duration_since(Instant::now())
+                .min(MAX_SLEEP),
+        );
+    }
+}
+
+fn sample(sampler: &Sampler, sources: &mut Sources, metric: Metric) {
+    match metric {
+        Metric::Cpu => {
+            sources.cpu.refresh_cpu_usage();
+            let usage = sources.cpu.cpus().iter().map(|cpu| cpu.cpu_usage());
+            publish(&sampler.cpu, usage.collect());
+        }
+        Metric::Memory => {
+            sources.memory.refresh_memory();
+            publish(
+                &sampler.memory,
+                Memory {
+                    total: sources.memory.total_memory(),
+                    used: sources.memory.used_memory(),
+                    available: sources.memory.available_memory(),
+                },
+            );
+        }
+        Metric::Network => {
+            sources.network.refresh();
+            let elapsed = seconds_since(&mut sources.network_read);
+            let (up, down) = sources
+                .network
+                .iter()
+                .fold((0, 0), |(up, down), (_, network)| {
+                    (up + network.transmitted(), down + network.received())
+                });
+            publish(
+                &sampler.network,
+                Throughput {
+                    up: up as f32 / elapsed,
+                    down: down as f32 / elapsed,
+                },
+            );
+        }
+        Metric::Disk => {
+            sources.disk.refresh_processes_specifics(
+                ProcessesToUpdate::All,
+                true,
+                ProcessRefreshKind::new().with_disk_usage(),
+            );
+            let elapsed = seconds_since(&mut sources.disk_read);
+            let (written, read) =
+                sources
+                    .disk
+                    .processes()
+                    .values()
+                    .fold((0, 0), |(written, read), process| {
+                        let disk = process.disk_usage();
+                        (written + disk.written_bytes, read + disk.read_bytes)
+                    });
+            publish(
+                &sampler.disk,
+                Throughput {
+                    up: written as f32 / elapsed,
+                    down: read as f32 / elapsed,
+                },
+            );
+        }
+        Metric::Leds => publish(&sampler.leds, indicators::read_leds()),
+        Metric::Processes => {
+            sources.processes.refresh_memory();
+            sources.processes.refresh_processes_specifics(
+                ProcessesToUpdate::All,
+                true,
+                ProcessRefreshKind::new()
+                    .with_cpu()
+                    .with_memory()
+                    .with_cmd(UpdateKind::OnlyIfNotSet),
+            );
+            let total_memory = sources.processes.total_memory() as f64;
+            let processes = sources
+                .processes
+                .processes()
+                .iter()
+                .map(|(pid, process)| Process {
+                    pid: *pid,
+                    name: process.name().to_string_lossy().into_owned(),
+                    command: format!("{:?}", process.cmd()),
+                    cpu_usage: process.cpu_usage(),
+                    memory_bytes: process.memory(),
+                    memory_percent: (process.memory() as f64 / total_memory * 100.0) as f32,
+                })
+                .collect();
+            publish(&sampler.processes, processes);
+        }
+    }
+}
+
+fn seconds_since(last: &mut Instant) -> f32 {
+    let now = Instant::now();
+    let elapsed = now.duration_since(*last).as_secs_f32().max(0.001);
+    *last = now;
+    elapsed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intervals_are_clamped() {
+        let settings: SamplerSettings =
+            toml::from_str("cpu_ms = 0\ndisk_ms = 10\nmemory_ms = 1000").unwrap();
+        assert_eq!(settings.cpu_ms, MIN_INTERVAL_MS);
+        assert_eq!(settings.disk_ms, MIN_INTERVAL_MS);
+        assert_eq!(settings.memory_ms, 1000);
+        assert_eq!(settings.network_ms, SamplerSettings::default().network_ms);
+    }
+}
@@ -0,0 +1,216 @@
+use std::ffi::CStr;
+use std::sync::Once;
+use zeroize::Zeroize;
+
+// Longest password we take, in bytes. The buffer never grows, so no copy of
+// it is left behind by a reallocation.
+const CAPACITY: usize = 1024;
+
+static MLOCK_WARNING: Once = Once::new();
+
+/// Password bytes that stay in RAM and are wiped when removed or dropped.
+/// Deliberately has no `Debug` or `Display`, use `as_str` where the text is
+/// really needed.
+pub struct Secret {
+    // CAPACITY bytes plus a NUL, everything past `len` is always zero. Its
+    // own page aligned mapping, so no other allocation shares its pages and
+    // the munlock on drop can't unlock anyone else's bytes.
+    buf: *mut u8,
+    len: usize,
+}
+
+// The mapping is owned like a Box
+unsafe impl Send for Secret {}
+unsafe impl Sync for Secret {}
+
+fn mapping_size() -> usize {
+    let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+    (CAPACITY + 1).div_ceil(page) * page
+}
+
+impl Secret {
+    pub fn new() -> Self {
+        let buf = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                mapping_size(),
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        if buf == libc::MAP_FAILED {
+            panic!(
+                "could not map the password buffer: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+        // Keeps the buffer out of swap. Fails past RLIMIT_MEMLOCK, which is
+        // worth a warning but not worth refusing to lock the screen over.
+        if unsafe { libc::mlock(buf, mapping_size()) } != 0 {
+            MLOCK_WARNING.call_once(|| {
+                eprintln!(
+                    "raylock: could not mlock the password buffer: {}",
+                    std::io::Error::last_os_error()
+                )
+            });
+        }
+        Secret {
+            buf: buf.cast(),
+            len: 0,
+        }
+    }
+
+    fn bytes(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.buf, CAPACITY + 1) }
+    }
+
+    fn bytes_mut(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.buf, CAPACITY + 1) }
+    }
+
+    /// Appends `ch`, false if it doesn't fit. NUL is dropped since C
+    /// backends couldn't see past it.
+    pub fn push(&mut self, ch: char) -> bool {
+        if ch == '\0' {
+            return true;
+        }
+        let width = ch.len_utf8();
+        if self.len + width > CAPACITY {
+            return false;
+        }
+        let len = self.len;
+        ch.encode_utf8(&mut self.bytes_mut()[len..len + width]);
+        self.len += width;
+        true
+    }
+
+    /// Removes the last character
+    pub fn pop(&mut self) {
+        if let Some(ch) = self.last_char() {
+            let start = self.len - ch.len_utf8();
+            let len = self.len;
+            self.bytes_mut()[start..len].zeroize();
+            self.len = start;
+        }
+    }
+
+    /// Removes the last word and the whitespace after it, like Ctrl+W in a
+    /// shell
+    pub fn pop_word(&mut self) {
+        while self.last_char().is_some_and(char::is_whitespace) {
+            self.pop();
+        }
+        while self.last_char().is_some_and(|ch| !ch.is_whitespace()) {
+            self.pop();
+        }
+    }
+
+    fn last_char(&self) -> Option<char> {
+        self.as_str().chars().next_back()
+    }
+
+    pub fn clear(&mut self) {
+        let len = self.len;
+        self.bytes_mut()[..len].zeroize();
+        self.len = 0;
+    }
+
+    /// Length in characters, what the ring draws a dot for
+    pub fn char_count(&self) -> usize {
+        self.as_str().chars().count()
+    }
+
+    pub fn as_str(&self) -> &str {
+        // Only whole chars are ever written
+        std::str::from_utf8(&self.bytes()[..self.len]).unwrap_or_default()
+    }
+
+    /// The password NUL terminated, for C APIs, without copying it
+    pub fn as_c_str(&self) -> &CStr {
+        CStr::from_bytes_until_nul(self.bytes()).unwrap_or_default()
+    }
+}
+
+impl Default for Secret {
+    fn default() -> Self {
+        Secret::new()
+    }
+}
+
+#[cfg(test)]
+impl From<&str> for Secret {
+    fn from(text: &str) -> Self {
+        let mut secret = Secret::new();
+        for ch in text.chars() {
+            assert!(secret.push(ch));
+        }
+        secret
+    }
+}
+
+impl Clone for Secret {
+    fn clone(&self) -> Self {
+        let mut copy = Secret::new();
+        copy.bytes_mut()[..self.len].copy_from_slice(&self.bytes()[..self.len]);
+        copy.len = self.len;
+        copy
+    }
+}
+
+impl Drop for Secret {
+    fn drop(&mut self) {
+        self.bytes_mut().zeroize();
+        unsafe {
+            libc::munlock(self.buf.cast(), mapping_size());
+            libc::munmap(self.buf.cast(), mapping_size());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Locked kB of the mapping at `address`, from /proc/self/smaps
+    fn locked_kb(address: *const u8) -> u64 {
+        let smaps = std::fs::read_to_string("/proc/self/smaps").unwrap();
+        let mut inside = false;
+        for line in smaps.lines() {
+            if let Some((range, _)) = line.split_once(' ').filter(|(r, _)| r.contains('-')) {
+                let (start, end) = range.split_once('-').unwrap();
+                let start = usize::from_str_radix(start, 16).unwrap_or(usize::MAX);
+                let end = usize::from_str_radix(end, 16).unwrap_or(0);
+                inside = (start..end).contains(&(address as usize));
+            } else if let Some(kb) = line.strip_prefix("Locked:").filter(|_| inside) {
+                return kb.trim().trim_end_matches(" kB").parse().unwrap();
+            }
+        }
+        panic!("no mapping at {:p}", address)
+    }
+
+    #[test]
+    fn dropping_a_clone_keeps_the_original_locked() {
+        let secret = Secret::from("hunter2");
+        if locked_kb(secret.buf) == 0 {
+            // Over RLIMIT_MEMLOCK, nothing to check
+            return;
+        }
+        drop(secret.clone());
+        assert!(locked_kb(secret.buf) > 0);
+        assert_eq!(secret.as_str(), "hunter2");
+    }
+
+    #[test]
+    fn edits_keep_the_buffer_nul_terminated() {
+        let mut secret = Secret::from("correct horse");
+        secret.pop_word();
+        assert_eq!(secret.as_c_str().to_bytes(), b"correct ");
+        secret.pop();
+        secret.push('!');
+        assert_eq!(secret.as_c_str().to_bytes(), b"correct!");
+        secret.clear();
+        assert_eq!(secret.char_count(), 0);
+    }
+}
@@ -0,0 +1,769 @@
+use glutin::config::{Config, ConfigSurfaceTypes, ConfigTemplateBuilder};
+use glutin::context::{ContextApi, ContextAttributesBuilder, PossiblyCurrentContext};
+use glutin::display::{Display, DisplayApiPreference};
+use glutin::prelude::*;
+use glutin::surface::{Surface, SurfaceAttributesBuilder, SwapInterval, WindowSurface};
+use raw_window_handle::{
+    RawDisplayHandle, RawWindowHandle, WaylandDisplayHandle, WaylandWindowHandle,
+};
+use smithay_client_toolkit::compositor::{CompositorHandler, CompositorState};
+use smithay_client_toolkit::output::{OutputHandler, OutputState};
+use smithay_client_toolkit::reexports::calloop::{self, EventLoop};
+use smithay_client_toolkit::reexports::calloop_wayland_source::WaylandSource;
+use smithay_client_toolkit::registry::{ProvidesRegistryState, RegistryState};
+use smithay_client_toolkit::seat::{Capability, SeatHandler, SeatState};
+use smithay_client_toolkit::session_lock::{
+    SessionLock, SessionLockHandler, SessionLockState, SessionLockSurface,
+    SessionLockSurfaceConfigure,
+};
+use smithay_client_toolkit::{
+    delegate_compositor, delegate_output, delegate_registry, delegate_seat, delegate_session_lock,
+    registry_handlers,
+};
+use std::fmt;
+use std::num::NonZeroU32;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use wayland_client::globals::{registry_queue_init, BindError, GlobalError};
+use wayland_client::protocol::{wl_keyboard, wl_output, wl_pointer, wl_seat, wl_surface};
+use wayland_client::{ConnectError, Connection, Dispatch, Proxy, QueueHandle, WEnum};
+
+use crate::events;
+use crate::keyboard::Keyboard;
+use crate::panes::Screen;
+use crate::ready;
+use crate::ExampleApp;
+
+const MAX_WAIT: Duration = Duration::from_secs(1);
+
+#[derive(Debug)]
+pub enum SessionLockError {
+    Connect(ConnectError),
+    Registry(GlobalError),
+    Bind(BindError),
+    Unsupported,
+    Xkb,
+    Egl(glutin::error::Error),
+    EventLoop(calloop::Error),
+    Refused,
+}
+
+impl fmt::Display for SessionLockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SessionLockError::Connect(e) => write!(f, "could not connect to wayland: {}", e),
+            SessionLockError::Registry(e) => write!(f, "wayland registry: {}", e),
+            SessionLockError::Bind(e) => write!(f, "wayland: {}", e),
+            SessionLockError::Unsupported => {
+                write!(f, "the compositor does not support ext-session-lock-v1")
+            }
+            SessionLockError::Xkb => write!(f, "could not load libxkbcommon"),
+            SessionLockError::Egl(e) => write!(f, "EGL: {}", e),
+            SessionLockError::EventLoop(e) => write!(f, "event loop: {}", e),
+            SessionLockError::Refused => {
+                write!(
+                    f,
+                    "the compositor refused the lock, is another locker running?"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for SessionLockError {}
+
+/// One egui context and GL context per lock surface, they draw at different
+/// sizes and scales and each keeps its own textures.
+struct Renderer {
+    egui_ctx: egui::Context,
+    painter: egui_glow::Painter,
+    gl_surface: Surface<WindowSurface>,
+    gl_context: PossiblyCurrentContext,
+}
+
+impl Renderer {
+    fn new(
+        display: &Display,
+        wl_surface: &wl_surface::WlSurface,
+        size: [u32; 2],
+    ) -> Result<Self, String> {
+        let mut window_handle = WaylandWindowHandle::empty();
+        window_handle.surface = wl_surface.id().as_ptr().cast();
+        let window_handle = RawWindowHandle::Wayland(window_handle);
+
+        let template = ConfigTemplateBuilder::new()
+            .with_alpha_size(8)
+            .with_surface_type(ConfigSurfaceTypes::WINDOW)
+            .build();
+        let config: Config = unsafe { display.find_configs(template) }
+            .map_err(|e| e.to_string())?
+            .next()
+            .ok_or("no usable EGL config")?;
+
+        // Desktop GL first, then GLES like eframe does
+        let context = unsafe {
+            display
+                .create_context(
+                    &config,
+                    &ContextAttributesBuilder::new().build(Some(window_handle)),
+                )
+                .or_else(|_| {
+                    display.create_context(
+                        &config,
+                        &ContextAttributesBuilder::new()
+                            .with_context_api(ContextApi::Gles(None))
+                            .build(Some(window_handle)),
+                    )
+                })
+        }
+        .map_err(|e| e.to_string())?;
+
+        let attributes = SurfaceAttributesBuilder::<WindowSurface>::new().build(
+            window_handle,
+            non_zero(size[0]),
+            non_zero(size[1]),
+        );
+        let gl_surface = unsafe { display.create_window_surface(&config, &attributes) }
+            .map_err(|e| e.to_string())?;
+        let gl_context = context
+            .make_current(&gl_surface)
+            .map_err(|e| e.to_string())?;
+        // Outputs that aren't visible never send frame callbacks, don't block on them
+        let _ = gl_surface.set_swap_interval(&gl_context, SwapInterval::DontWait);
+
+        let gl = unsafe {
+            glow::Context::from_loader_function_cstr(|name| display.get_proc_address(name))
+        };
+        // egui_glow wants an Arc, the context never leaves this thread
+        #[allow(clippy::arc_with_non_send_sync)]
+        let gl = Arc::new(gl);
+        let painter = egui_glow::Painter::new(gl, "", None).map_err(|e| e.to_string())?;
+
+        Ok(Renderer {
+            egui_ctx: egui::Context::default(),
+            painter,
+            gl_surface,
+            gl_context,
+        })
+    }
+
+    fn resize(&self, size: [u32; 2]) {
+        self.gl_surface
+            .resize(&self.gl_context, non_zero(size[0]), non_zero(size[1]));
+    }
+
+    fn draw(
+        &mut self,
+        app: &ExampleApp,
+        screen: &mut Screen,
+        show_ring: bool,
+        input: egui::RawInput,
+        size: [u32; 2],
+    ) -> Duration {
+        if self.gl_context.make_current(&self.gl_surface).is_err() {
+            return MAX_WAIT;
+        }
+
+        let clear_color = app.clear_color();
+        let output = self
+            .egui_ctx
+            .run(input, |ctx| app.show(ctx, screen, show_ring));
+        let primitives = self
+            .egui_ctx
+            .tessellate(output.shapes, output.pixels_per_point);
+
+        self.painter.clear(size, clear_color);
+        self.painter.paint_and_update_textures(
+            size,
+            output.pixels_per_point,
+            &primitives,
+            &output.textures_delta,
+        );
+        let _ = self.gl_surface.swap_buffers(&self.gl_context);
+
+        output
+            .viewport_output
+            .get(&egui::ViewportId::ROOT)
+            .map_or(MAX_WAIT, |viewport| viewport.repaint_delay)
+    }
+}
+
+impl Drop for Renderer {
+    fn drop(&mut self) {
+        let _ = self.gl_context.make_current(&self.gl_surface);
+        self.painter.destroy();
+    }
+}
+
+struct LockSurface {
+    surface: SessionLockSurface,
+    output: wl_output::WlOutput,
+    // Connector name, e.g. "DP-1", if the compositor told us
+    name: Option<String>,
+    screen: Screen,
+    // Logical size from the last configure, zero until the first one
+    size: (u32, u32),
+    scale: i32,
+    renderer: Option<Renderer>,
+    // None when the surface should be drawn as soon as possible
+    next_frame: Option<Instant>,
+}
+
+impl LockSurface {
+    fn size_px(&self) -> [u32; 2] {
+        [
+            self.size.0 * self.scale as u32,
+            self.size.1 * self.scale as u32,
+        ]
+    }
+}
+
+struct LockState {
+    app: ExampleApp,
+    registry_state: RegistryState,
+    output_state: OutputState,
+    seat_state: SeatState,
+    compositor_state: CompositorState,
+    session_lock_state: SessionLockState,
+    display: Display,
+    session_lock: Option<SessionLock>,
+    surfaces: Vec<LockSurface>,
+    keyboard: Keyboard,
+    wl_keyboard: Option<wl_keyboard::WlKeyboard>,
+    wl_pointer: Option<wl_pointer::WlPointer>,
+    // Last pointer position on whichever surface it's over
+    pointer_pos: egui::Pos2,
+    // Pointer input since the last frame, handed to the app once per frame.
+    // Keys go to it as they arrive.
+    events: Vec<egui::Event>,
+    finished: bool,
+}
+
+/// Locks the session with ext-session-lock-v1 and draws `app` on every
+/// output until the password is accepted. If raylock dies before that the
+/// compositor keeps the session locked.
+pub fn run(app: ExampleApp) -> Result<(), SessionLockError> {
+    let conn = Connection::connect_to_env().map_err(SessionLockError::Connect)?;
+    let (globals, event_queue) = registry_queue_init(&conn).map_err(SessionLockError::Registry)?;
+    let qh = event_queue.handle();
+
+    let mut event_loop: EventLoop<LockState> =
+        EventLoop::try_new().map_err(SessionLockError::EventLoop)?;
+    WaylandSource::new(conn.clone(), event_queue)
+        .insert(event_loop.handle())
+        .map_err(|e| SessionLockError::EventLoop(e.error))?;
+
+    let mut display_handle = WaylandDisplayHandle::empty();
+    display_handle.display = conn.backend().display_ptr().cast();
+    let display = unsafe {
+        Display::new(
+            RawDisplayHandle::Wayland(display_handle),
+            DisplayApiPreference::Egl,
+        )
+    }
+    .map_err(SessionLockError::Egl)?;
+
+    let mut state = LockState {
+        app,
+        registry_state: RegistryState::new(&globals),
+        output_state: OutputState::new(&globals, &qh),
+        seat_state: SeatState::new(&globals, &qh),
+        compositor_state: CompositorState::bind(&globals, &qh).map_err(SessionLockError::Bind)?,
+        session_lock_state: SessionLockState::new(&globals, &qh),
+        display,
+        session_lock: None,
+        surfaces: Vec::new(),
+        keyboard: Keyboard::new().ok_or(SessionLockError::Xkb)?,
+        wl_keyboard: None,
+        wl_pointer: None,
+        pointer_pos: egui::Pos2::ZERO,
+        events: Vec::new(),
+        finished: false,
+    };
+    state.session_lock = Some(
+        state
+            .session_lock_state
+            .lock(&qh)
+            .map_err(|_| SessionLockError::Unsupported)?,
+    );
+
+    let start = Instant::now();
+    loop {
+        let until_repeat = state
+            .keyboard
+            .next_repeat()
+            .map_or(MAX_WAIT, |at| at.saturating_duration_since(Instant::now()));
+        event_loop
+            .dispatch(state.until_next_frame().min(until_repeat), &mut state)
+            .map_err(SessionLockError::EventLoop)?;
+        let app = &mut state.app;
+        let mut repeated = false;
+        state.keyboard.repeat(Instant::now(), |input| {
+            repeated = true;
+            app.key_input(input);
+        });
+        if repeated {
+            state.redraw_all();
+        }
+
+        if state.finished {
+            // `finished` before `locked` means the lock was never granted
+            let was_locked = state
+                .session_lock
+                .take()
+                .is_some_and(|lock| lock.is_locked());
+            return if was_locked {
+                Ok(())
+            } else {
+                Err(SessionLockError::Refused)
+            };
+        }
+
+        let events = std::mem::take(&mut state.events);
+        if !events.is_empty() {
+            state.app.handle_events(&events);
+            state.redraw_all();
+        }
+        if state.app.poll_power_menu() {
+            state.redraw_all();
+        }
+
+        if state.app.is_unlocked() {
+            // Dropping the lock sends unlock_and_destroy, make sure it arrives
+            state.surfaces.clear();
+            state.session_lock.take();
+            let _ = conn.roundtrip();
+            events::emit(events::Event::Unlock, &state.app.attempts());
+            return Ok(());
+        }
+
+        state.draw(start.elapsed().as_secs_f64());
+    }
+}
+
+impl LockState {
+    fn create_surface(&mut self, qh: &QueueHandle<Self>, output: wl_output::WlOutput) {
+        let Some(session_lock) = &self.session_lock else {
+            return;
+        };
+        let wl_surface = self.compositor_state.create_surface(qh);
+        let surface = session_lock.create_lock_surface(wl_surface, &output, qh);
+        let info = self.output_state.info(&output);
+        let scale = info.as_ref().map_or(1, |info| info.scale_factor.max(1));
+        let name = info.as_ref().and_then(|info| info.name.clone());
+        let resolution = info.as_ref().and_then(|info| {
+            info.modes
+                .iter()
+                .find(|mode| mode.current)
+                .map(|mode| mode.dimensions)
+        });
+        let screen = self.app.screen_for(name.as_deref(), resolution);
+        self.surfaces.push(LockSurface {
+            surface,
+            output,
+            name,
+            screen,
+            size: (0, 0),
+            scale,
+            renderer: None,
+            next_frame: None,
+        });
+        // The ring may move to the new output
+        self.redraw_all();
+    }
+
+    // Lock keys and layout switches arrive as modifier events
+    fn update_indicators(&mut self) {
+        let status = self.keyboard.status();
+        if status != self.app.keyboard {
+            self.app.keyboard = status;
+            self.redraw_all();
+        }
+    }
+
+    fn redraw_all(&mut self) {
+        for surface in &mut self.surfaces {
+            surface.next_frame = None;
+        }
+    }
+
+    fn until_next_frame(&self) -> Duration {
+        let now = Instant::now();
+        self.surfaces
+            .iter()
+            .filter(|surface| surface.renderer.is_some())
+            .map(|surface| {
+                surface
+                    .next_frame
+                    .map_or(Duration::ZERO, |next| next.saturating_duration_since(now))
+            })
+            .min()
+            .unwrap_or(MAX_WAIT)
+            .min(MAX_WAIT)
+    }
+
+    fn surface_mut(&mut self, wl_surface: &wl_surface::WlSurface) -> Option<&mut LockSurface> {
+        self.surfaces
+            .iter_mut()
+            .find(|surface| surface.surface.wl_surface() == wl_surface)
+    }
+
+    fn draw(&mut self, time: f64) {
+        let primary_connected = self
+            .app
+            .settings
+            .primary_output
+            .as_ref()
+            .is_some_and(|primary| {
+                self.surfaces
+                    .iter()
+                    .any(|surface| surface.name.as_ref() == Some(primary))
+            });
+        let now = Instant::now();
+        for surface in &mut self.surfaces {
+            let size_px = surface.size_px();
+            let Some(renderer) = &mut surface.renderer else {
+                continue;
+            };
+            if surface.next_frame.is_some_and(|next| next > now) {
+                continue;
+            }
+
+            let mut input = egui::RawInput {
+                screen_rect: Some(egui::Rect::from_min_size(
+                    egui::Pos2::ZERO,
+                    egui::vec2(surface.size.0 as f32, surface.size.1 as f32),
+                )),
+                time: Some(time),
+                focused: true,
+                ..Default::default()
+            };
+            input
+                .viewports
+                .entry(egui::ViewportId::ROOT)
+                .or_default()
+                .native_pixels_per_point = Some(surface.scale as f32);
+
+            let show_ring = self
+                .app
+                .shows_ring(surface.name.as_deref(), primary_connected);
+            let delay = renderer.draw(&self.app, &mut surface.screen, show_ring, input, size_px);
+            surface.next_frame = Some(now + delay.min(MAX_WAIT));
+        }
+
+        // Locked, every output has a surface and every surface a frame
+        let locked = self
+            .session_lock
+            .as_ref()
+            .is_some_and(|lock| lock.is_locked());
+        let all_covered = self.surfaces.len() >= self.output_state.outputs().count();
+        let all_drawn = self
+            .surfaces
+            .iter()
+            .all(|surface| surface.renderer.is_some() && surface.next_frame.is_some());
+        if locked && !self.surfaces.is_empty() && all_covered && all_drawn {
+            ready::signal();
+        }
+    }
+}
+
+impl SessionLockHandler for LockState {
+    fn locked(&mut self, _conn: &Connection, qh: &QueueHandle<Self>, _session_lock: SessionLock) {
+        events::emit(events::Event::Lock, &self.app.attempts());
+        for output in self.output_state.outputs() {
+            self.create_surface(qh, output);
+        }
+    }
+
+    fn finished(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _session_lock: SessionLock,
+    ) {
+        self.finished = true;
+    }
+
+    fn configure(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        session_lock_surface: SessionLockSurface,
+        configure: SessionLockSurfaceConfigure,
+        _serial: u32,
+    ) {
+        let display = self.display.clone();
+        let Some(surface) = self.surface_mut(session_lock_surface.wl_surface()) else {
+            return;
+        };
+        surface.size = configure.new_size;
+        surface.next_frame = None;
+        surface.surface.wl_surface().set_buffer_scale(surface.scale);
+
+        let size_px = surface.size_px();
+        match &surface.renderer {
+            Some(renderer) => renderer.resize(size_px),
+            None => match Renderer::new(&display, surface.surface.wl_surface(), size_px) {
+                Ok(renderer) => surface.renderer = Some(renderer),
+                // The session stays locked, typing the password still works
+                Err(e) => eprintln!("raylock: could not draw the lock screen: {}", e),
+            },
+        }
+    }
+}
+
+impl CompositorHandler for LockState {
+    fn scale_factor_changed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        wl_surface: &wl_surface::WlSurface,
+        new_factor: i32,
+    ) {
+        let Some(surface) = self.surface_mut(wl_surface) else {
+            return;
+        };
+        surface.scale = new_factor.max(1);
+        surface.next_frame = None;
+        surface.surface.wl_surface().set_buffer_scale(surface.scale);
+        if let Some(renderer) = &surface.renderer {
+            renderer.resize(surface.size_px());
+        }
+    }
+
+    fn transform_changed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _surface: &wl_surface::WlSurface,
+        _new_transform: wl_output::Transform,
+    ) {
+    }
+
+    fn frame(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _surface: &wl_surface::WlSurface,
+        _time: u32,
+    ) {
+    }
+}
+
+impl OutputHandler for LockState {
+    fn output_state(&mut self) -> &mut OutputState {
+        &mut self.output_state
+    }
+
+    // Outputs plugged in while locked need a lock surface too
+    fn new_output(
+        &mut self,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+        output: wl_output::WlOutput,
+    ) {
+        if self
+            .session_lock
+            .as_ref()
+            .is_some_and(|lock| lock.is_locked())
+        {
+            self.create_surface(qh, output);
+        }
+    }
+
+    fn update_output(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _output: wl_output::WlOutput,
+    ) {
+    }
+
+    fn output_destroyed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        output: wl_output::WlOutput,
+    ) {
+        self.surfaces.retain(|surface| surface.output != output);
+        self.redraw_all();
+    }
+}
+
+impl SeatHandler for LockState {
+    fn seat_state(&mut self) -> &mut SeatState {
+        &mut self.seat_state
+    }
+
+    fn new_seat(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _seat: wl_seat::WlSeat) {}
+
+    fn new_capability(
+        &mut self,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+        seat: wl_seat::WlSeat,
+        capability: Capability,
+    ) {
+        if capability == Capability::Keyboard && self.wl_keyboard.is_none() {
+            self.wl_keyboard = Some(seat.get_keyboard(qh, ()));
+        }
+        if capability == Capability::Pointer && self.wl_pointer.is_none() {
+            self.wl_pointer = Some(seat.get_pointer(qh, ()));
+        }
+    }
+
+    fn remove_capability(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _seat: wl_seat::WlSeat,
+        capability: Capability,
+    ) {
+        if capability == Capability::Keyboard {
+            if let Some(keyboard) = self.wl_keyboard.take() {
+                keyboard.release();
+            }
+        }
+        if capability == Capability::Pointer {
+            if let Some(pointer) = self.wl_pointer.take() {
+                pointer.release();
+            }
+        }
+    }
+
+    fn remove_seat(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _seat: wl_seat::WlSeat) {
+    }
+}
+
+impl Dispatch<wl_keyboard::WlKeyboard, ()> for LockState {
+    fn event(
+        state: &mut Self,
+        _keyboard: &wl_keyboard::WlKeyboard,
+        event: wl_keyboard::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        match event {
+            wl_keyboard::Event::Keymap {
+                format: WEnum::Value(wl_keyboard::KeymapFormat::XkbV1),
+                fd,
+                size,
+            } => {
+                state.keyboard.set_keymap(fd, size as usize);
+                state.update_indicators();
+            }
+            wl_keyboard::Event::Modifiers {
+                mods_depressed,
+                mods_latched,
+                mods_locked,
+                group,
+                ..
+            } => {
+                state
+                    .keyboard
+                    .update_modifiers(mods_depressed, mods_latched, mods_locked, group);
+                state.update_indicators();
+            }
+            wl_keyboard::Event::Key {
+                key,
+                state: WEnum::Value(key_state),
+                ..
+            } => {
+                let pressed = key_state == wl_keyboard::KeyState::Pressed;
+                let app = &mut state.app;
+                state
+                    .keyboard
+                    .key(key, pressed, |input| app.key_input(input));
+                state.redraw_all();
+            }
+            wl_keyboard::Event::RepeatInfo { rate, delay } => {
+                state.keyboard.set_repeat_info(rate, delay);
+            }
+            wl_keyboard::Event::Leave { .. } => state.keyboard.stop_repeat(),
+            _ => {}
+        }
+    }
+}
+
+// The pointer only wakes the screen and ends the grace period, so it's
+// passed on without caring which output it's on
+impl Dispatch<wl_pointer::WlPointer, ()> for LockState {
+    fn event(
+        state: &mut Self,
+        _pointer: &wl_pointer::WlPointer,
+        event: wl_pointer::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        match event {
+            // Where the pointer already was, so the first motion after it
+            // is seen as one
+            wl_pointer::Event::Enter {
+                surface_x,
+                surface_y,
+                ..
+            }
+            | wl_pointer::Event::Motion {
+                surface_x,
+                surface_y,
+                ..
+            } => {
+                state.pointer_pos = egui::pos2(surface_x as f32, surface_y as f32);
+                state
+                    .events
+                    .push(egui::Event::PointerMoved(state.pointer_pos));
+            }
+            wl_pointer::Event::Button {
+                button,
+                state: WEnum::Value(button_state),
+                ..
+            } => {
+                // Linux input event codes
+                let button = match button {
+                    0x111 => egui::PointerButton::Secondary,
+                    0x112 => egui::PointerButton::Middle,
+                    _ => egui::PointerButton::Primary,
+                };
+                state.events.push(egui::Event::PointerButton {
+                    pos: state.pointer_pos,
+                    button,
+                    pressed: button_state == wl_pointer::ButtonState::Pressed,
+                    modifiers: egui::Modifiers::NONE,
+                });
+            }
+            wl_pointer::Event::Axis { axis, value, .. } => {
+                let delta = match axis {
+                    WEnum::Value(wl_pointer::Axis::HorizontalScroll) => {
+                        egui::vec2(-value as f32, 0.)
+                    }
+                    _ => egui::vec2(0., -value as f32),
+                };
+                state.events.push(egui::Event::MouseWheel {
+                    unit: egui::MouseWheelUnit::Point,
+                    delta,
+                    modifiers: egui::Modifiers::NONE,
+                });
+            }
+            _ => {}
+        }
+    }
+}
+
+impl ProvidesRegistryState for LockState {
+    fn registry(&mut self) -> &mut RegistryState {
+        &mut self.registry_state
+    }
+    registry_handlers![OutputState, SeatState];
+}
+
+fn non_zero(value: u32) -> NonZeroU32 {
+    NonZeroU32::new(value).unwrap_or(NonZeroU32::MIN)
+}
+
+delegate_compositor!(LockState);
+delegate_output!(LockState);
+delegate_seat!(LockState);
+delegate_session_lock!(LockState);
+delegate_registry!(LockState);
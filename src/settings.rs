@@ -0,0 +1,185 @@
+use serde::Deserialize;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::auth::Backend;
+use crate::backoff::BackoffSettings;
+use crate::cli::Args;
+use crate::hooks::HookSettings;
+use crate::idle::IdleSettings;
+use crate::keys::KeyBindings;
+use crate::power::PowerSettings;
+use crate::sampler::SamplerSettings;
+
+const CONFIG_FILENAME: &str = "config.toml";
+const SYSTEM_CONFIG_DIR: &str = "/etc/raylock";
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    pub mode: LockMode,
+    pub auth: AuthSettings,
+    // Output that gets the password ring, every output when unset
+    pub primary_output: Option<String>,
+    pub outputs: Vec<OutputSettings>,
+    // 0 turns the grace period off
+    pub grace_secs: u64,
+    pub sampler: SamplerSettings,
+    pub keys: KeyBindings,
+    pub backoff: BackoffSettings,
+    pub hooks: HookSettings,
+    pub idle: IdleSettings,
+    pub power: PowerSettings,
+}
+
+/// A layout for the output called `name` (e.g. "DP-1") or with this
+/// `resolution` (e.g. "2560x1440")
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OutputSettings {
+    pub name: Option<String>,
+    pub resolution: Option<String>,
+    pub layout: PathBuf,
+}
+
+impl OutputSettings {
+    fn matches_resolution(&self, size: (i32, i32)) -> bool {
+        self.resolution
+            .as_deref()
+            .and_then(|res| res.split_once('x'))
+            .is_some_and(|(w, h)| w.trim().parse() == Ok(size.0) && h.trim().parse() == Ok(size.1))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum LockMode {
+    /// A fullscreen window plus sway's `lock` binding mode
+    Window,
+    /// ext-session-lock-v1, the compositor stays locked if raylock dies
+    #[default]
+    SessionLock,
+}
+
+impl FromStr for LockMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "window" => Ok(LockMode::Window),
+            "session-lock" => Ok(LockMode::SessionLock),
+            _ => Err(format!(
+                "unknown lock mode '{}' (expected window or session-lock)",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthSettings {
+    pub backend: Backend,
+    // Asked when `backend` can't give an answer, like sudo missing
+    pub fallback: Option<Backend>,
+    pub pam_service: String,
+    // Only used by the mock backend
+    #[cfg(feature = "mock-auth")]
+    pub mock_password: String,
+    #[cfg(feature = "mock-auth")]
+    pub mock_delay_ms: u64,
+}
+
+impl Default for AuthSettings {
+    fn default() -> Self {
+        AuthSettings {
+            backend: Backend::Pam,
+            fallback: None,
+            pam_service: "raylock".to_string(),
+            #[cfg(feature = "mock-auth")]
+            mock_password: String::new(),
+            #[cfg(feature = "mock-auth")]
+            mock_delay_ms: 0,
+        }
+    }
+}
+
+/// `$XDG_CONFIG_HOME/raylock` (or `~/.config/raylock`), then `/etc/raylock`
+pub fn config_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if let Some(dir) = env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        dirs.push(PathBuf::from(dir).join("raylock"));
+    } else if let Some(home) = env::var_os("HOME") {
+        dirs.push(PathBuf::from(home).join(".config").join("raylock"));
+    }
+    dirs.push(PathBuf::from(SYSTEM_CONFIG_DIR));
+    dirs
+}
+
+/// First existing `filename` in the config directories
+pub fn find_config_file(filename: &str) -> Option<PathBuf> {
+    config_dirs()
+        .into_iter()
+        .map(|dir| dir.join(filename))
+        .find(|path| path.is_file())
+}
+
+impl Settings {
+    /// Layout file for an output, a name match wins over a resolution match
+    pub fn output_layout(&self, name: Option<&str>, size: Option<(i32, i32)>) -> Option<&Path> {
+        let by_name = name.and_then(|name| {
+            self.outputs
+                .iter()
+                .find(|output| output.name.as_deref() == Some(name))
+        });
+        let by_resolution = || {
+            size.and_then(|size| {
+                self.outputs
+                    .iter()
+                    .find(|output| output.name.is_none() && output.matches_resolution(size))
+            })
+        };
+        by_name
+            .or_else(by_resolution)
+            .map(|output| output.layout.as_path())
+    }
+
+    /// Loads the config file given on the command line or found in the
+    /// config directories, then applies command line overrides. A broken
+    /// config file is reported and replaced by the defaults.
+    pub fn load(args: &Args) -> Self {
+        let path = args
+            .config
+            .clone()
+            .or_else(|| find_config_file(CONFIG_FILENAME));
+
+        let mut settings = match path {
+            Some(path) => Settings::from_file(&path).unwrap_or_else(|e| {
+                eprintln!("raylock: {}: {}, using defaults", path.display(), e);
+                Settings::default()
+            }),
+            None => Settings::default(),
+        };
+
+        if let Some(mode) = args.mode {
+            settings.mode = mode;
+        }
+        if let Some(backend) = args.auth {
+            settings.auth.backend = backend;
+        }
+        if let Some(service) = &args.pam_service {
+            settings.auth.pam_service = service.clone();
+        }
+        if args.no_grace {
+            settings.grace_secs = 0;
+        }
+
+        settings
+    }
+
+    fn from_file(path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        toml::from_str(&contents).map_err(|e| e.to_string())
+    }
+}
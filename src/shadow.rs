@@ -0,0 +1,117 @@
+use libloading::Library;
+use std::ffi::{CStr, CString};
+use std::fmt;
+use std::os::raw::c_char;
+use std::sync::Mutex;
+
+use crate::auth;
+use crate::secret::Secret;
+
+const SHADOW_FILEPATH: &str = "/etc/shadow";
+// Loaded at runtime like libpam, see pam.rs
+const LIBCRYPT: &str = "libcrypt.so.1";
+
+type CryptFn = unsafe extern "C" fn(phrase: *const c_char, setting: *const c_char) -> *mut c_char;
+
+// crypt() returns a pointer into a static buffer
+static CRYPT_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug)]
+pub enum ShadowError {
+    NoUser,
+    Read(std::io::Error),
+    NoEntry(String),
+    NoPassword(String),
+    Load(libloading::Error),
+    Crypt,
+    Privileges(std::io::Error),
+}
+
+impl fmt::Display for ShadowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShadowError::NoUser => write!(f, "could not determine the current user"),
+            ShadowError::Read(e) => write!(f, "could not read {}: {}", SHADOW_FILEPATH, e),
+            ShadowError::NoEntry(user) => write!(f, "no {} entry for {}", SHADOW_FILEPATH, user),
+            ShadowError::NoPassword(user) => write!(f, "{} has no usable password hash", user),
+            ShadowError::Load(e) => write!(f, "could not load {}: {}", LIBCRYPT, e),
+            ShadowError::Crypt => write!(f, "crypt() rejected the stored hash"),
+            ShadowError::Privileges(e) => write!(f, "could not drop privileges: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ShadowError {}
+
+/// Looks up the password hash for `user`. Needs root, so setuid installs
+/// read it once at startup and then call `drop_privileges`.
+pub fn read_hash(user: &str) -> Result<String, ShadowError> {
+    let contents = std::fs::read_to_string(SHADOW_FILEPATH).map_err(ShadowError::Read)?;
+    let hash = contents
+        .lines()
+        .find_map(|line| {
+            let mut fields = line.split(':');
+            (fields.next() == Some(user)).then(|| fields.next().unwrap_or("").to_string())
+        })
+        .ok_or_else(|| ShadowError::NoEntry(user.to_string()))?;
+
+    // "!" and "*" prefixes mark locked accounts, an empty hash means no password
+    if hash.is_empty() || hash.starts_with('!') || hash.starts_with('*') {
+        return Err(ShadowError::NoPassword(user.to_string()));
+    }
+    Ok(hash)
+}
+
+/// The current user's hash, see `read_hash`
+pub fn read_own_hash() -> Result<String, ShadowError> {
+    let user = auth::current_user().ok_or(ShadowError::NoUser)?;
+    read_hash(&user)
+}
+
+pub fn check(password: &Secret, hash: &str) -> Result<bool, ShadowError> {
+    let phrase = password.as_c_str();
+    let setting = CString::new(hash).map_err(|_| ShadowError::Crypt)?;
+
+    let lib = unsafe { Library::new(LIBCRYPT) }.map_err(ShadowError::Load)?;
+    let crypt = unsafe { *lib.get::<CryptFn>(b"crypt\0").map_err(ShadowError::Load)? };
+
+    let _guard = CRYPT_LOCK.lock().unwrap();
+    let result = unsafe { crypt(phrase.as_ptr(), setting.as_ptr()) };
+    // libxcrypt signals failure with a null pointer or a hash starting with '*'
+    if result.is_null() {
+        return Err(ShadowError::Crypt);
+    }
+    let computed = unsafe { CStr::from_ptr(result) }.to_bytes();
+    if computed.first() == Some(&b'*') {
+        return Err(ShadowError::Crypt);
+    }
+
+    Ok(constant_time_eq(computed, hash.as_bytes()))
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Gives up setuid/setgid root once the hash has been read. Everything the
+/// user can point raylock at, like --config, is only opened after this.
+pub fn drop_privileges() -> Result<(), ShadowError> {
+    unsafe {
+        if libc::geteuid() == libc::getuid() && libc::getegid() == libc::getgid() {
+            return Ok(());
+        }
+        if libc::setgid(libc::getgid()) != 0 || libc::setuid(libc::getuid()) != 0 {
+            return Err(ShadowError::Privileges(std::io::Error::last_os_error()));
+        }
+        // Make sure root can't be regained
+        if libc::setuid(0) != -1 {
+            return Err(ShadowError::Privileges(std::io::Error::other(
+                "still able to become root",
+            )));
+        }
+    }
+    Ok(())
+}
@@ -1,16 +1,116 @@
-use egui::{Color32, Painter, Pos2, Rect, Vec2};
-use rand::Rng;
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::fmt;
-use std::sync::MutexGuard;
 // use serde::
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::auth::AuthErrorKind;
+use crate::backoff::Record;
+use crate::secret::Secret;
+
+// How long the dots take to fall into the middle of the ring after a clear
+pub const CLEAR_ANIMATION: Duration = Duration::from_millis(300);
+// How long the ring shows a verdict before settling, and before unlocking
+pub const RESULT_ANIMATION: Duration = Duration::from_millis(400);
+
+/// Where the last submitted password is. Each phase remembers when it began
+/// so the ring can animate into it.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum AuthPhase {
+    #[default]
+    Idle,
+    // Handed to the authenticator, waiting for its verdict
+    Verifying(Instant),
+    Failed(Instant),
+    // The authenticator couldn't answer, the attempt doesn't count
+    Error(Instant),
+    Succeeded(Instant),
+}
+
+impl AuthPhase {
+    /// Progress from 0 to 1 of the animation into a verdict, `None` once
+    /// it's over or when there is no verdict
+    pub fn result_progress(&self) -> Option<f32> {
+        let (AuthPhase::Failed(at) | AuthPhase::Error(at) | AuthPhase::Succeeded(at)) = self else {
+            return None;
+        };
+        let progress = at.elapsed().as_secs_f32() / RESULT_ANIMATION.as_secs_f32();
+        (progress < 1.).then_some(progress)
+    }
+}
 
 #[derive(Default)]
 pub struct AuthState {
-    pub password: String,
+    pub password: Secret,
     pub to_be_submitted: bool,
+    pub phase: AuthPhase,
     pub failed_attempts: u16,
+    // End and total length of the current lockout, see backoff.rs
+    pub lockout: Option<(SystemTime, Duration)>,
+    // Why the last check couldn't be done, shown until the next verdict
+    pub error: Option<AuthErrorKind>,
+    // Set over the control socket, shown on every output
+    pub banner: Option<String>,
+    // When the password was last cleared and how many dots it had
+    pub cleared: Option<(Instant, usize)>,
+    // Start and length of the grace period, when any input unlocks
+    pub grace: Option<(Instant, Duration)>,
+    // A character didn't fit, nothing more is typed until one is deleted
+    pub full: bool,
+}
+
+impl AuthState {
+    pub fn attempts(&self) -> Record {
+        Record {
+            failed_attempts: self.failed_attempts,
+            lockout: self.lockout,
+        }
+    }
+
+    pub fn verifying(&self) -> bool {
+        matches!(self.phase, AuthPhase::Verifying(_))
+    }
+
+    /// Whether the password can be edited. The one being checked is the one
+    /// on screen, so it's held until the verdict is in.
+    pub fn accepts_input(&self) -> bool {
+        !self.attempts().locked_out()
+            && !self.verifying()
+            && !matches!(self.phase, AuthPhase::Succeeded(_))
+    }
+
+    /// Adds a typed character, false once the password is full
+    pub fn type_char(&mut self, ch: char) -> bool {
+        self.full = self.full || !self.password.push(ch);
+        !self.full
+    }
+
+    /// True once the success animation has played, the frontend then
+    /// releases the lock and exits
+    pub fn unlocked(&self) -> bool {
+        matches!(self.phase, AuthPhase::Succeeded(at) if at.elapsed() >= RESULT_ANIMATION)
+    }
+
+    /// Time left and the total length, `None` once it's over
+    pub fn grace_left(&self) -> Option<(Duration, Duration)> {
+        let (started, length) = self.grace?;
+        let left = length.checked_sub(started.elapsed())?;
+        (!left.is_zero()).then_some((left, length))
+    }
+
+    /// Wipes the password and starts the ring's clear animation
+    pub fn clear_password(&mut self) {
+        let dots = self.password.char_count();
+        if dots > 0 {
+            self.cleared = Some((Instant::now(), dots));
+        }
+        self.password.clear();
+        self.full = false;
+    }
+
+    /// Progress of the clear animation from 0 to 1, `None` when it's over
+    pub fn clear_progress(&self) -> Option<(f32, usize)> {
+        let (started, dots) = self.cleared?;
+        let progress = started.elapsed().as_secs_f32() / CLEAR_ANIMATION.as_secs_f32();
+        (progress < 1.).then_some((progress, dots))
+    }
 }
 
 // #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
@@ -185,3 +285,23 @@ pub struct AuthState {
 //         }
 //     }
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_full_password_takes_nothing_more() {
+        let mut state = AuthState::default();
+        while state.type_char('€') {}
+        assert!(state.full);
+        let typed = state.password.char_count();
+        // A narrower character would fit, but typing has stopped
+        assert!(!state.type_char('a'));
+        assert_eq!(state.password.char_count(), typed);
+
+        state.clear_password();
+        assert!(!state.full);
+        assert!(state.type_char('a'));
+    }
+}
@@ -0,0 +1,450 @@
+use serde::Deserialize;
+use serde_json::Value;
+use std::env;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const MAGIC: &[u8; 6] = b"i3-ipc";
+const HEADER_LEN: usize = MAGIC.len() + 8;
+
+pub const RUN_COMMAND: u32 = 0;
+const SUBSCRIBE: u32 = 2;
+const GET_OUTPUTS: u32 = 3;
+const GET_BINDING_STATE: u32 = 12;
+const GET_INPUTS: u32 = 100;
+
+const REPLY_TIMEOUT: Duration = Duration::from_secs(2);
+// The length comes from the other end and is only trusted up to here
+const MAX_MESSAGE_LEN: usize = 64 * 1024 * 1024;
+
+// Events have the high bit set, the rest is the event type
+const EVENT_BIT: u32 = 1 << 31;
+
+#[derive(Debug)]
+pub enum IpcError {
+    NoSocket,
+    Io(io::Error),
+    Protocol(String),
+    Json(serde_json::Error),
+    Command(String),
+    ModeNotApplied { expected: String, actual: String },
+    TooManyOutputs(Vec<String>),
+}
+
+impl fmt::Display for IpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IpcError::NoSocket => write!(f, "$SWAYSOCK is not set, is sway running?"),
+            IpcError::Io(e) => write!(f, "sway IPC: {}", e),
+            IpcError::Protocol(message) => write!(f, "sway IPC: {}", message),
+            IpcError::Json(e) => write!(f, "sway IPC: bad reply: {}", e),
+            IpcError::Command(message) => write!(f, "sway rejected the command: {}", message),
+            IpcError::ModeNotApplied { expected, actual } => write!(
+                f,
+                "sway is in mode '{}' instead of '{}', is mode \"{}\" in the sway config?",
+                actual, expected, expected
+            ),
+            IpcError::TooManyOutputs(names) => write!(
+                f,
+                "the window mode only covers one output and {} are active ({})",
+                names.len(),
+                names.join(", ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for IpcError {}
+
+impl From<io::Error> for IpcError {
+    fn from(e: io::Error) -> Self {
+        IpcError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for IpcError {
+    fn from(e: serde_json::Error) -> Self {
+        IpcError::Json(e)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EventKind {
+    Workspace,
+    Output,
+    Mode,
+    Window,
+    Binding,
+    Shutdown,
+    Tick,
+    Input,
+    Other(u32),
+}
+
+impl EventKind {
+    fn from_type(kind: u32) -> Self {
+        match kind & !EVENT_BIT {
+            0 => EventKind::Workspace,
+            1 => EventKind::Output,
+            2 => EventKind::Mode,
+            3 => EventKind::Window,
+            5 => EventKind::Binding,
+            6 => EventKind::Shutdown,
+            7 => EventKind::Tick,
+            21 => EventKind::Input,
+            other => EventKind::Other(other),
+        }
+    }
+}
+
+pub struct Event {
+    pub kind: EventKind,
+    pub payload: Value,
+}
+
+#[derive(Deserialize)]
+struct CommandOutcome {
+    success: bool,
+    error: Option<String>,
+}
+
+pub fn socket_path() -> Result<PathBuf, IpcError> {
+    env::var_os("SWAYSOCK")
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+        .ok_or(IpcError::NoSocket)
+}
+
+/// A connection to sway's IPC socket (the i3 protocol).
+pub struct SwayIpc {
+    stream: UnixStream,
+}
+
+impl SwayIpc {
+    pub fn connect() -> Result<Self, IpcError> {
+        SwayIpc::connect_to(&socket_path()?)
+    }
+
+    pub fn connect_to(path: &Path) -> Result<Self, IpcError> {
+        let stream = UnixStream::connect(path)?;
+        // A hung sway shouldn't hang unlocking with it
+        stream.set_read_timeout(Some(REPLY_TIMEOUT))?;
+        stream.set_write_timeout(Some(REPLY_TIMEOUT))?;
+        Ok(SwayIpc { stream })
+    }
+
+    /// Runs sway commands, failing if any of them was rejected.
+    pub fn run_command(&mut self, command: &str) -> Result<(), IpcError> {
+        let reply = self.request(RUN_COMMAND, command)?;
+        let outcomes: Vec<CommandOutcome> = serde_json::from_value(reply)?;
+        match outcomes.into_iter().find(|outcome| !outcome.success) {
+            Some(failed) => Err(IpcError::Command(
+                failed.error.unwrap_or_else(|| command.to_string()),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    pub fn binding_state(&mut self) -> Result<String, IpcError> {
+        let reply = self.request(GET_BINDING_STATE, "")?;
+        reply["name"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| IpcError::Protocol("binding state without a name".to_string()))
+    }
+
+    pub fn active_outputs(&mut self) -> Result<Vec<String>, IpcError> {
+        let reply = self.request(GET_OUTPUTS, "")?;
+        let outputs = reply
+            .as_array()
+            .ok_or_else(|| IpcError::Protocol("outputs reply is not a list".to_string()))?;
+        Ok(outputs
+            .iter()
+            .filter(|output| output["active"] == true)
+            .filter_map(|output| output["name"].as_str().map(str::to_string))
+            .collect())
+    }
+
+    /// Active xkb layout of the first keyboard that has one
+    pub fn keyboard_layout(&mut self) -> Result<Option<String>, IpcError> {
+        let reply = self.request(GET_INPUTS, "")?;
+        let inputs = reply
+            .as_array()
+            .ok_or_else(|| IpcError::Protocol("inputs reply is not a list".to_string()))?;
+        Ok(inputs.iter().find_map(layout_name))
+    }
+
+    /// Sway accepts `mode` for modes missing from the config, so this
+    /// checks that the switch took.
+    pub fn set_mode(&mut self, mode: &str) -> Result<(), IpcError> {
+        self.run_command(&format!("mode \"{}\"", mode))?;
+        let actual = self.binding_state()?;
+        if actual != mode {
+            return Err(IpcError::ModeNotApplied {
+                expected: mode.to_string(),
+                actual,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn subscribe(mut self, events: &[&str]) -> Result<EventStream, IpcError> {
+        let reply = self.request(SUBSCRIBE, &serde_json::to_string(events)?)?;
+        if reply["success"] != Value::Bool(true) {
+            return Err(IpcError::Command(format!(
+                "could not subscribe to {:?}",
+                events
+            )));
+        }
+        self.stream.set_read_timeout(None)?;
+        Ok(EventStream { ipc: self })
+    }
+
+    fn request(&mut self, kind: u32, payload: &str) -> Result<Value, IpcError> {
+        self.send(kind, payload)?;
+        loop {
+            let (reply_kind, body) = self.read_message()?;
+            // Events can arrive between a request and its reply
+            if reply_kind & EVENT_BIT != 0 {
+                continue;
+            }
+            if reply_kind != kind {
+                return Err(IpcError::Protocol(format!(
+                    "expected a reply of type {}, got {}",
+                    kind, reply_kind
+                )));
+            }
+            return Ok(serde_json::from_slice(&body)?);
+        }
+    }
+
+    fn send(&mut self, kind: u32, payload: &str) -> Result<(), IpcError> {
+        let mut message = Vec::with_capacity(HEADER_LEN + payload.len());
+        message.extend_from_slice(MAGIC);
+        message.extend_from_slice(&(payload.len() as u32).to_ne_bytes());
+        message.extend_from_slice(&kind.to_ne_bytes());
+        message.extend_from_slice(payload.as_bytes());
+        self.stream.write_all(&message)?;
+        Ok(())
+    }
+
+    fn read_message(&mut self) -> Result<(u32, Vec<u8>), IpcError> {
+        let mut header = [0u8; HEADER_LEN];
+        self.stream.read_exact(&mut header)?;
+        if &header[..MAGIC.len()] != MAGIC {
+            return Err(IpcError::Protocol("bad magic".to_string()));
+        }
+        let len = u32::from_ne_bytes(header[6..10].try_into().unwrap()) as usize;
+        let kind = u32::from_ne_bytes(header[10..14].try_into().unwrap());
+        if len > MAX_MESSAGE_LEN {
+            return Err(IpcError::Protocol(format!(
+                "{} byte message is too long",
+                len
+            )));
+        }
+
+        let mut body = vec![0u8; len];
+        self.stream.read_exact(&mut body)?;
+        Ok((kind, body))
+    }
+}
+
+pub fn layout_name(input: &Value) -> Option<String> {
+    if input["type"] != "keyboard" {
+        return None;
+    }
+    input["xkb_active_layout_name"].as_str().map(str::to_string)
+}
+
+pub struct EventStream {
+    ipc: SwayIpc,
+}
+
+impl EventStream {
+    pub fn next_event(&mut self) -> Result<Event, IpcError> {
+        loop {
+            let (kind, body) = self.ipc.read_message()?;
+            if kind & EVENT_BIT == 0 {
+                continue;
+            }
+            return Ok(Event {
+                kind: EventKind::from_type(kind),
+                payload: serde_json::from_slice(&body)?,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use std::os::unix::net::UnixListener;
+    use std::thread::{self, JoinHandle};
+
+    pub fn frame(kind: u32, payload: &[u8]) -> Vec<u8> {
+        let mut frame = MAGIC.to_vec();
+        frame.extend_from_slice(&(payload.len() as u32).to_ne_bytes());
+        frame.extend_from_slice(&kind.to_ne_bytes());
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    // Reads one request and returns its type and payload
+    pub fn read_request(stream: &mut UnixStream) -> (u32, String) {
+        let mut header = [0u8; HEADER_LEN];
+        stream.read_exact(&mut header).unwrap();
+        assert_eq!(&header[..MAGIC.len()], MAGIC);
+        let len = u32::from_ne_bytes(header[6..10].try_into().unwrap()) as usize;
+        let kind = u32::from_ne_bytes(header[10..14].try_into().unwrap());
+        let mut payload = vec![0u8; len];
+        stream.read_exact(&mut payload).unwrap();
+        (kind, String::from_utf8(payload).unwrap())
+    }
+
+    /// A sway stand-in on its own socket. Each request gets the next
+    /// canned reply, written as is, and the requests are handed back at the
+    /// end.
+    fn fake_sway(name: &str, replies: Vec<Vec<u8>>) -> (PathBuf, JoinHandle<Vec<(u32, String)>>) {
+        let path =
+            env::temp_dir().join(format!("raylock-ipc-{}-{}.sock", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        let socket = path.clone();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let _ = std::fs::remove_file(socket);
+            let mut requests = Vec::new();
+            for reply in replies {
+                requests.push(read_request(&mut stream));
+                stream.write_all(&reply).unwrap();
+            }
+            requests
+        });
+        (path, server)
+    }
+
+    #[test]
+    fn runs_commands() {
+        let (path, server) = fake_sway(
+            "command",
+            vec![frame(RUN_COMMAND, br#"[{"success": true}]"#)],
+        );
+        let mut ipc = SwayIpc::connect_to(&path).unwrap();
+        ipc.run_command("mode \"lock\"").unwrap();
+        assert_eq!(
+            server.join().unwrap(),
+            [(RUN_COMMAND, "mode \"lock\"".to_string())]
+        );
+    }
+
+    #[test]
+    fn reports_rejected_commands() {
+        let (path, _server) = fake_sway(
+            "rejected",
+            vec![frame(
+                RUN_COMMAND,
+                br#"[{"success": true}, {"success": false, "error": "Unknown command"}]"#,
+            )],
+        );
+        let mut ipc = SwayIpc::connect_to(&path).unwrap();
+        match ipc.run_command("nop; bogus") {
+            Err(IpcError::Command(message)) => assert_eq!(message, "Unknown command"),
+            other => panic!("expected a rejection, got {:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn skips_events_before_the_reply() {
+        let mut reply = frame(EVENT_BIT | 2, br#"{"change": "lock"}"#);
+        reply.extend(frame(GET_BINDING_STATE, br#"{"name": "lock"}"#));
+        let (path, _server) = fake_sway("events", vec![reply]);
+        let mut ipc = SwayIpc::connect_to(&path).unwrap();
+        assert_eq!(ipc.binding_state().unwrap(), "lock");
+    }
+
+    #[test]
+    fn checks_the_mode_was_applied() {
+        let (path, _server) = fake_sway(
+            "mode",
+            vec![
+                frame(RUN_COMMAND, br#"[{"success": true}]"#),
+                frame(GET_BINDING_STATE, br#"{"name": "default"}"#),
+            ],
+        );
+        let mut ipc = SwayIpc::connect_to(&path).unwrap();
+        match ipc.set_mode("lock") {
+            Err(IpcError::ModeNotApplied { expected, actual }) => {
+                assert_eq!((expected.as_str(), actual.as_str()), ("lock", "default"))
+            }
+            other => panic!("expected ModeNotApplied, got {:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn lists_active_outputs() {
+        let (path, _server) = fake_sway(
+            "outputs",
+            vec![frame(
+                GET_OUTPUTS,
+                br#"[{"name": "eDP-1", "active": true}, {"name": "HDMI-A-1", "active": false},
+                     {"name": "DP-1", "active": true}]"#,
+            )],
+        );
+        let mut ipc = SwayIpc::connect_to(&path).unwrap();
+        assert_eq!(ipc.active_outputs().unwrap(), ["eDP-1", "DP-1"]);
+    }
+
+    #[test]
+    fn fails_on_a_short_read() {
+        // Promises 100 bytes, sends 10 and hangs up
+        let mut reply = frame(GET_BINDING_STATE, &[b' '; 100]);
+        reply.truncate(HEADER_LEN + 10);
+        let (path, _server) = fake_sway("short", vec![reply]);
+        let mut ipc = SwayIpc::connect_to(&path).unwrap();
+        match ipc.binding_state() {
+            Err(IpcError::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof),
+            other => panic!("expected an early EOF, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_a_bad_magic() {
+        let mut reply = frame(GET_BINDING_STATE, br#"{"name": "lock"}"#);
+        reply[..MAGIC.len()].copy_from_slice(b"i3-bad");
+        let (path, _server) = fake_sway("magic", vec![reply]);
+        let mut ipc = SwayIpc::connect_to(&path).unwrap();
+        assert!(matches!(ipc.binding_state(), Err(IpcError::Protocol(_))));
+    }
+
+    #[test]
+    fn refuses_oversized_messages() {
+        // Only the header, a 4 GiB body is never read or allocated
+        let mut reply = frame(GET_BINDING_STATE, b"");
+        reply[6..10].copy_from_slice(&u32::MAX.to_ne_bytes());
+        let (path, _server) = fake_sway("oversized", vec![reply]);
+        let mut ipc = SwayIpc::connect_to(&path).unwrap();
+        match ipc.binding_state() {
+            Err(IpcError::Protocol(message)) => assert!(message.contains("too long")),
+            other => panic!("expected a protocol error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn streams_subscribed_events() {
+        let mut reply = frame(SUBSCRIBE, br#"{"success": true}"#);
+        reply.extend(frame(EVENT_BIT | 21, br#"{"change": "xkb_layout"}"#));
+        let (path, server) = fake_sway("subscribe", vec![reply]);
+        let ipc = SwayIpc::connect_to(&path).unwrap();
+        let mut events = ipc.subscribe(&["input"]).unwrap();
+        let event = events.next_event().unwrap();
+        assert_eq!(event.kind, EventKind::Input);
+        assert_eq!(event.payload["change"], "xkb_layout");
+        assert_eq!(
+            server.join().unwrap(),
+            [(SUBSCRIBE, r#"["input"]"#.to_string())]
+        );
+    }
+}
@@ -1,8 +1,9 @@
-use egui::{Color32, Pos2, Rect, Response, Stroke, Vec2};
+use egui::{Color32, Pos2, Rect};
 use std::time::Instant;
-use sysinfo::{Pid, System};
 
-const UPDATE_INTERVAL: f32 = 0.5; // seconds
+use crate::sampler::{self, Metric, Process};
+use crate::theme::TableTheme;
+
 pub const BAR_HEIGHT: f32 = 16.0;
 pub const ROW_HEIGHT: f32 = 24.0;
 const COLUMN_PADDING: f32 = 10.0;
@@ -14,38 +15,24 @@ pub enum SortColumn {
     Memory,
 }
 
-struct ProcessInfo {
-    pid: Pid,
-    name: String,
-    command: String,
-    cpu_usage: f32,
-    memory_bytes: u64,
-    memory_percent: f32,
-}
-
 pub struct ProcessTable {
-    sys: System,
-    last_update: Instant,
+    // Sample the rows were last taken from
+    seen: u64,
     last_swap: Instant,
-    processes: Vec<ProcessInfo>,
+    processes: Vec<Process>,
     sort_by: SortColumn,
     name_width: usize,
+    #[allow(dead_code)]
     command_width: usize,
     pub row_count: usize,
 }
 
-fn rem_first_and_last(value: String, num: usize) -> String {
-    let mut chars = value.chars();
-    chars.next();
-    chars.next_back();
-    chars.take(num).collect::<String>()
-}
-
 impl ProcessTable {
     pub fn new(name_width: usize, command_width: usize, row_count: usize) -> Self {
+        sampler::get().want(Metric::Processes);
+
         Self {
-            sys: System::new_all(),
-            last_update: Instant::now(),
+            seen: 0,
             last_swap: Instant::now(),
             processes: Vec::new(),
             sort_by: SortColumn::Cpu,
@@ -55,63 +42,54 @@ impl ProcessTable {
         }
     }
 
-    pub fn set_sort(&mut self, sort_by: SortColumn) {
-        self.sort_by = sort_by;
-    }
-
     pub fn update(&mut self) {
         let now = Instant::now();
-        if now.duration_since(self.last_update).as_secs_f32() < UPDATE_INTERVAL {
-            return;
-        }
+        let mut swapped = false;
         if now.duration_since(self.last_swap).as_secs_f32() > SWAP_INTERVAL {
             self.sort_by = match self.sort_by {
                 SortColumn::Cpu => SortColumn::Memory,
                 SortColumn::Memory => SortColumn::Cpu,
             };
             self.last_swap = now;
+            swapped = true;
         }
 
-        self.sys.refresh_all();
-
-        let total_memory = self.sys.total_memory() as f64;
-
-        // Collect process information
-        self.processes = self
-            .sys
-            .processes()
-            .iter()
-            .map(|(pid, proc)| ProcessInfo {
-                pid: *pid,
-                name: proc.name().to_str().unwrap().to_string(),
-                command: format!("{:?}", proc.cmd()),
-                cpu_usage: proc.cpu_usage(),
-                memory_bytes: proc.memory(),
-                memory_percent: (proc.memory() as f64 / total_memory * 100.0) as f32,
-            })
-            .collect();
+        let sample = sampler::get().processes.load();
+        if sample.seq == self.seen && !swapped {
+            return;
+        }
 
         // Sort processes based on selected criterion
+        let mut processes: Vec<&Process> = sample.value.iter().collect();
         match self.sort_by {
             SortColumn::Cpu => {
-                self.processes
-                    .sort_by(|a, b| b.cpu_usage.partial_cmp(&a.cpu_usage).unwrap());
+                processes.sort_by(|a, b| b.cpu_usage.total_cmp(&a.cpu_usage));
             }
             SortColumn::Memory => {
-                self.processes
-                    .sort_by_key(|p| std::cmp::Reverse(p.memory_bytes));
+                processes.sort_by_key(|p| std::cmp::Reverse(p.memory_bytes));
             }
         }
 
         // Keep only top N processes
-        self.processes.truncate(self.row_count);
+        self.processes = processes
+            .into_iter()
+            .take(self.row_count)
+            .cloned()
+            .collect();
 
-        self.last_update = now;
+        self.seen = sample.seq;
     }
 
-    fn draw_bar(&self, painter: &egui::Painter, rect: Rect, percentage: f32, color: Color32) {
+    fn draw_bar(
+        &self,
+        painter: &egui::Painter,
+        rect: Rect,
+        percentage: f32,
+        color: Color32,
+        theme: &TableTheme,
+    ) {
         // Background
-        painter.rect_filled(rect, 0.0, Color32::from_gray(40));
+        painter.rect_filled(rect, 0.0, theme.bar_background);
 
         // Foreground bar
         let bar_width = rect.width() * (percentage / 100.0).min(1.0);
@@ -124,19 +102,19 @@ impl ProcessTable {
             rect.center(),
             egui::Align2::CENTER_CENTER,
             text,
-            egui::FontId::proportional(12.0),
-            Color32::WHITE,
+            egui::FontId::proportional(theme.bar_font_size),
+            theme.text_color,
         );
     }
 
-    fn draw_header(&self, painter: &egui::Painter, rect: Rect) -> f32 {
-        let text_color = Color32::LIGHT_GRAY;
+    fn draw_header(&self, painter: &egui::Painter, rect: Rect, theme: &TableTheme) -> f32 {
+        let text_color = theme.header_text_color;
         let header_height = 24.0;
         let header_rect =
             Rect::from_min_max(rect.min, Pos2::new(rect.max.x, rect.min.y + header_height));
 
         // Background
-        painter.rect_filled(header_rect, 0.0, Color32::from_gray(30));
+        painter.rect_filled(header_rect, 0.0, theme.header_background);
 
         let mut x = rect.min.x + COLUMN_PADDING;
 
@@ -145,7 +123,7 @@ impl ProcessTable {
             Pos2::new(x, header_rect.center().y),
             egui::Align2::LEFT_CENTER,
             "PID",
-            egui::FontId::proportional(14.0),
+            egui::FontId::proportional(theme.font_size),
             text_color,
         );
         x += rect.width() * 0.1;
@@ -155,7 +133,7 @@ impl ProcessTable {
             Pos2::new(x, header_rect.center().y),
             egui::Align2::LEFT_CENTER,
             "Name",
-            egui::FontId::proportional(14.0),
+            egui::FontId::proportional(theme.font_size),
             text_color,
         );
         x += rect.width() * 0.2;
@@ -165,7 +143,7 @@ impl ProcessTable {
             Pos2::new(x, header_rect.center().y),
             egui::Align2::LEFT_CENTER,
             "Command",
-            egui::FontId::proportional(14.0),
+            egui::FontId::proportional(theme.font_size),
             text_color,
         );
         x += rect.width() * 0.5;
@@ -175,7 +153,7 @@ impl ProcessTable {
             Pos2::new(x, header_rect.center().y),
             egui::Align2::LEFT_CENTER,
             "CPU %",
-            egui::FontId::proportional(14.0),
+            egui::FontId::proportional(theme.font_size),
             text_color,
         );
         x += rect.width() * 0.1;
@@ -185,15 +163,15 @@ impl ProcessTable {
             Pos2::new(x, header_rect.center().y),
             egui::Align2::LEFT_CENTER,
             "Memory %",
-            egui::FontId::proportional(14.0),
+            egui::FontId::proportional(theme.font_size),
             text_color,
         );
 
         header_height
     }
 
-    pub fn render(&mut self, painter: &egui::Painter, rect: Rect) {
-        let header_height = self.draw_header(painter, rect);
+    pub fn render(&mut self, painter: &egui::Painter, rect: Rect, theme: &TableTheme) {
+        let header_height = self.draw_header(painter, rect, theme);
         let content_rect =
             Rect::from_min_max(Pos2::new(rect.min.x, rect.min.y + header_height), rect.max);
 
@@ -209,9 +187,9 @@ impl ProcessTable {
                 row_rect,
                 0.0,
                 if i % 2 == 0 {
-                    Color32::from_gray(25)
+                    theme.row_background
                 } else {
-                    Color32::from_gray(20)
+                    theme.alternate_row_background
                 },
             );
 
@@ -223,8 +201,8 @@ impl ProcessTable {
                 Pos2::new(x, text_y),
                 egui::Align2::LEFT_CENTER,
                 process.pid.to_string(),
-                egui::FontId::proportional(14.0),
-                Color32::WHITE,
+                egui::FontId::proportional(theme.font_size),
+                theme.text_color,
             );
             x += rect.width() * 0.1;
 
@@ -237,8 +215,8 @@ impl ProcessTable {
                     .chars()
                     .take(self.name_width)
                     .collect::<String>(),
-                egui::FontId::proportional(14.0),
-                Color32::WHITE,
+                egui::FontId::proportional(theme.font_size),
+                theme.text_color,
             );
             x += rect.width() * 0.2;
 
@@ -258,8 +236,8 @@ impl ProcessTable {
                     .collect::<String>(),
                 // .chars()
                 // .take(self.command_width).collect::<String>(),
-                egui::FontId::proportional(14.0),
-                Color32::WHITE,
+                egui::FontId::proportional(theme.font_size),
+                theme.text_color,
             );
             x += rect.width() * 0.5;
 
@@ -274,7 +252,8 @@ impl ProcessTable {
                 painter,
                 cpu_bar_rect,
                 process.cpu_usage,
-                Color32::from_rgb(46, 194, 126),
+                theme.cpu_bar_color,
+                theme,
             );
             x += rect.width() * 0.1;
 
@@ -290,7 +269,8 @@ impl ProcessTable {
                 painter,
                 mem_bar_rect,
                 process.memory_percent,
-                Color32::from_rgb(194, 137, 46),
+                theme.memory_bar_color,
+                theme,
             );
         }
     }
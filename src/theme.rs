@@ -0,0 +1,317 @@
+use egui::{Color32, FontId, Rgba, Stroke};
+use serde::{Deserialize, Deserializer};
+use std::f32::consts::SQRT_2;
+use std::path::{Path, PathBuf};
+
+use crate::settings::find_config_file;
+
+const THEME_FILENAMES: [&str; 2] = ["theme.toml", "theme.json"];
+
+/// Colours, strokes, font sizes and geometry of the lock screen. Every
+/// field has a default, so a theme file only lists what it changes.
+/// Colours are written as "#rrggbb" or "#rrggbbaa".
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    #[serde(deserialize_with = "color")]
+    pub text_color: Color32,
+    // Behind the panes, what the window or lock surface is cleared to
+    #[serde(deserialize_with = "color")]
+    pub backdrop: Color32,
+    #[serde(deserialize_with = "color")]
+    pub pane_background: Color32,
+    pub pane_outline_width: f32,
+    pub inner_outline_width: f32,
+    pub title_font_size: f32,
+    pub text_font_size: f32,
+    pub pane_gap: f32,
+    // Defaults to reaching the login ring, see `corner_cut()`
+    corner_cut: Option<f32>,
+    pub graph: GraphTheme,
+    pub table: TableTheme,
+    pub login: LoginTheme,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GraphTheme {
+    #[serde(deserialize_with = "stroke")]
+    pub stroke: Stroke,
+    #[serde(deserialize_with = "stroke")]
+    pub up_stroke: Stroke,
+    #[serde(deserialize_with = "stroke")]
+    pub down_stroke: Stroke,
+    #[serde(deserialize_with = "color")]
+    pub grid_color: Color32,
+    #[serde(deserialize_with = "color")]
+    pub label_color: Color32,
+    pub label_font_size: f32,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TableTheme {
+    #[serde(deserialize_with = "color")]
+    pub text_color: Color32,
+    #[serde(deserialize_with = "color")]
+    pub header_text_color: Color32,
+    #[serde(deserialize_with = "color")]
+    pub header_background: Color32,
+    #[serde(deserialize_with = "color")]
+    pub row_background: Color32,
+    #[serde(deserialize_with = "color")]
+    pub alternate_row_background: Color32,
+    #[serde(deserialize_with = "color")]
+    pub bar_background: Color32,
+    #[serde(deserialize_with = "color")]
+    pub cpu_bar_color: Color32,
+    #[serde(deserialize_with = "color")]
+    pub memory_bar_color: Color32,
+    pub font_size: f32,
+    pub bar_font_size: f32,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoginTheme {
+    pub circle_radius: f32,
+    // Degrees, clockwise from 3 o'clock
+    pub start_angle: f32,
+    pub dot_radius: f32,
+    #[serde(deserialize_with = "color")]
+    pub dot_fill: Color32,
+    #[serde(deserialize_with = "stroke")]
+    pub dot_stroke: Stroke,
+    #[serde(deserialize_with = "stroke")]
+    pub line_stroke: Stroke,
+    #[serde(deserialize_with = "stroke")]
+    pub fail_ring: Stroke,
+    pub fail_count_radius: f32,
+    #[serde(deserialize_with = "color")]
+    pub fail_dot_fill: Color32,
+    #[serde(deserialize_with = "stroke")]
+    pub fail_dot_stroke: Stroke,
+    // Caps Lock warning under the ring
+    #[serde(deserialize_with = "color")]
+    pub warning_color: Color32,
+    pub indicator_font_size: f32,
+    // Arc and seconds shown while input is locked after failures
+    #[serde(deserialize_with = "stroke")]
+    pub lockout_stroke: Stroke,
+    pub countdown_font_size: f32,
+    // Spinner while the password is checked and the ring closing on success
+    #[serde(deserialize_with = "stroke")]
+    pub verifying_stroke: Stroke,
+    #[serde(deserialize_with = "stroke")]
+    pub success_stroke: Stroke,
+    // Arc running down while any input still unlocks
+    #[serde(deserialize_with = "stroke")]
+    pub grace_stroke: Stroke,
+    // Message sent over the control socket
+    #[serde(deserialize_with = "color")]
+    pub banner_fill: Color32,
+    pub banner_font_size: f32,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            text_color: Color32::from_rgb(255, 255, 255),
+            backdrop: Rgba::from_rgba_unmultiplied(0.1, 0.1, 0.1, 0.9).into(),
+            pane_background: Color32::from_rgba_premultiplied(10, 10, 10, 230),
+            pane_outline_width: 0.5,
+            inner_outline_width: 0.25,
+            title_font_size: 25.,
+            text_font_size: 16.,
+            pane_gap: 6.,
+            corner_cut: None,
+            graph: GraphTheme::default(),
+            table: TableTheme::default(),
+            login: LoginTheme::default(),
+        }
+    }
+}
+
+impl Default for GraphTheme {
+    fn default() -> Self {
+        GraphTheme {
+            stroke: Stroke::new(0.5, Color32::from_rgba_premultiplied(255, 255, 255, 1)),
+            up_stroke: Stroke::new(1.5, Color32::from_rgba_premultiplied(0, 255, 255, 1)),
+            down_stroke: Stroke::new(1.5, Color32::from_rgba_premultiplied(255, 64, 4, 1)),
+            grid_color: Color32::from_gray(40),
+            label_color: Color32::LIGHT_GRAY,
+            label_font_size: 12.,
+        }
+    }
+}
+
+impl Default for TableTheme {
+    fn default() -> Self {
+        TableTheme {
+            text_color: Color32::WHITE,
+            header_text_color: Color32::LIGHT_GRAY,
+            header_background: Color32::from_gray(30),
+            row_background: Color32::from_gray(25),
+            alternate_row_background: Color32::from_gray(20),
+            bar_background: Color32::from_gray(40),
+            cpu_bar_color: Color32::from_rgb(46, 194, 126),
+            memory_bar_color: Color32::from_rgb(194, 137, 46),
+            font_size: 14.,
+            bar_font_size: 12.,
+        }
+    }
+}
+
+impl Default for LoginTheme {
+    fn default() -> Self {
+        let fail_color = Color32::from_rgb(184, 41, 11);
+        LoginTheme {
+            circle_radius: 50.,
+            start_angle: -45.,
+            dot_radius: 4.,
+            dot_fill: Color32::TRANSPARENT,
+            dot_stroke: Stroke::new(2., Color32::from_rgb(255, 255, 255)),
+            line_stroke: Stroke::new(2., Color32::from_rgb(255, 255, 255)),
+            fail_ring: Stroke::new(5., fail_color),
+            fail_count_radius: 15.,
+            fail_dot_fill: Color32::TRANSPARENT,
+            fail_dot_stroke: Stroke::new(2., fail_color),
+            warning_color: Color32::from_rgb(230, 170, 20),
+            indicator_font_size: 14.,
+            lockout_stroke: Stroke::new(3., fail_color),
+            countdown_font_size: 20.,
+            verifying_stroke: Stroke::new(3., Color32::from_rgb(90, 160, 230)),
+            success_stroke: Stroke::new(3., Color32::from_rgb(60, 190, 90)),
+            grace_stroke: Stroke::new(3., Color32::from_rgb(230, 230, 230)),
+            banner_fill: Color32::from_black_alpha(200),
+            banner_font_size: 18.,
+        }
+    }
+}
+
+impl Theme {
+    /// Loads `path`, or the first theme.toml/theme.json in the config
+    /// directories. A broken theme is reported and replaced by the defaults.
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let path = path.or_else(|| THEME_FILENAMES.into_iter().find_map(find_config_file));
+        match path {
+            Some(path) => Theme::from_file(&path).unwrap_or_else(|e| {
+                eprintln!(
+                    "raylock: {}: {}, using the default theme",
+                    path.display(),
+                    e
+                );
+                Theme::default()
+            }),
+            None => Theme::default(),
+        }
+    }
+
+    fn from_file(path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        if path.extension().is_some_and(|ext| ext == "json") {
+            serde_json::from_str(&contents).map_err(|e| e.to_string())
+        } else {
+            toml::from_str(&contents).map_err(|e| e.to_string())
+        }
+    }
+
+    /// How far the pane corners around the login ring are cut back
+    pub fn corner_cut(&self) -> f32 {
+        self.corner_cut.unwrap_or(self.login.circle_radius * SQRT_2)
+    }
+
+    pub fn title_font(&self) -> FontId {
+        FontId::monospace(self.title_font_size)
+    }
+
+    pub fn text_font(&self) -> FontId {
+        FontId::monospace(self.text_font_size)
+    }
+
+    /// `backdrop` as the linear rgba egui clears with
+    pub fn clear_color(&self) -> [f32; 4] {
+        Rgba::from(self.backdrop).to_array()
+    }
+}
+
+fn parse_color(s: &str) -> Option<Color32> {
+    let hex = s.strip_prefix('#')?;
+    if !hex.is_ascii() || (hex.len() != 6 && hex.len() != 8) {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    let alpha = if hex.len() == 8 { channel(6)? } else { 255 };
+    Some(Color32::from_rgba_unmultiplied(
+        channel(0)?,
+        channel(2)?,
+        channel(4)?,
+        alpha,
+    ))
+}
+
+fn color<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color32, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    parse_color(&s).ok_or_else(|| {
+        serde::de::Error::custom(format!(
+            "invalid colour '{}', expected #rrggbb or #rrggbbaa",
+            s
+        ))
+    })
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StrokeDef {
+    width: f32,
+    #[serde(deserialize_with = "color")]
+    color: Color32,
+}
+
+fn stroke<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Stroke, D::Error> {
+    let def = StrokeDef::deserialize(deserializer)?;
+    Ok(Stroke::new(def.width, def.color))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_partial_theme_keeps_the_other_defaults() {
+        let theme: Theme = toml::from_str(
+            r##"
+            text_color = "#11223344"
+
+            [login]
+            circle_radius = 80.0
+            "##,
+        )
+        .unwrap();
+        assert_eq!(
+            theme.text_color,
+            Color32::from_rgba_unmultiplied(0x11, 0x22, 0x33, 0x44)
+        );
+        assert_eq!(theme.login.circle_radius, 80.);
+
+        let default = Theme::default();
+        assert_eq!(theme.pane_gap, default.pane_gap);
+        assert_eq!(theme.login.dot_radius, default.login.dot_radius);
+        assert_eq!(theme.table.font_size, default.table.font_size);
+        // The corners follow the bigger ring unless they're set themselves
+        assert_eq!(theme.corner_cut(), 80. * SQRT_2);
+    }
+
+    #[test]
+    fn colours_are_checked() {
+        assert_eq!(parse_color("#ff0000"), Some(Color32::from_rgb(255, 0, 0)));
+        assert_eq!(
+            parse_color("#00ff0080"),
+            Some(Color32::from_rgba_unmultiplied(0, 255, 0, 128))
+        );
+        for bad in ["ff0000", "#ff00", "#gg0000", "#ff0000ff00"] {
+            assert_eq!(parse_color(bad), None, "{}", bad);
+        }
+        assert!(toml::from_str::<Theme>("corner_cut = 10.0\nbackdrop = \"red\"").is_err());
+    }
+}
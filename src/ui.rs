@@ -1,18 +1,21 @@
-use crate::configs::*;
+use crate::indicators::KeyboardStatus;
 use crate::panes;
 use crate::panes::PaneInstance;
 use crate::structs;
+use crate::theme::{LoginTheme, Theme};
 // use panes::PaneRenderer;
 // use crate::structs::cur_context;
 // use crate::structs::windowTypes;
 
 use eframe::egui;
-use egui::Color32;
-use egui::Shape;
+use egui::{Color32, Stroke};
 use egui::{Pos2, Rect, Vec2};
-use panes::Pane;
 use std::f32::consts::PI;
 
+// Seconds per turn of the spinner, and how much of the ring it covers
+const SPINNER_PERIOD: f32 = 1.2;
+const SPINNER_LENGTH: f32 = 0.25;
+
 use std::ops::Deref;
 use std::sync::MutexGuard;
 
@@ -24,49 +27,48 @@ fn rot_circle(i: i16, center: Pos2, rad: f32, offset_ang: f32, ang_per_num: f32)
         })
 }
 
-fn add_Pos2(add: Vec2, points: Vec<Pos2>) -> Vec<Pos2> {
+fn add_pos2(add: Vec2, points: Vec<Pos2>) -> Vec<Pos2> {
     let mut points = points;
-    for i in 0..points.len() {
-        points[i] = points[i] + add;
+    for point in points.iter_mut() {
+        *point += add;
     }
     points
 }
 
-fn mult_Pos2(mult: f32, points: Vec<Pos2>) -> Vec<Pos2> {
+fn mult_pos2(mult: f32, points: Vec<Pos2>) -> Vec<Pos2> {
     let mut points = points;
-    let vec = Vec2 { x: mult, y: mult };
-    for i in 0..points.len() {
-        points[i] = points[i] * mult;
+    for point in points.iter_mut() {
+        *point = *point * mult;
     }
     points
 }
 
 fn rotate_90(points: Vec<Pos2>) -> Vec<Pos2> {
     let mut points = points;
-    for i in 0..points.len() {
-        points[i] = Pos2 {
-            x: -points[i].y,
-            y: points[i].x,
+    for point in points.iter_mut() {
+        *point = Pos2 {
+            x: -point.y,
+            y: point.x,
         }
     }
     points
 }
 fn rotate_180(points: Vec<Pos2>) -> Vec<Pos2> {
     let mut points = points;
-    for i in 0..points.len() {
-        points[i] = Pos2 {
-            x: -points[i].x,
-            y: -points[i].y,
+    for point in points.iter_mut() {
+        *point = Pos2 {
+            x: -point.x,
+            y: -point.y,
         }
     }
     points
 }
 fn rotate_270(points: Vec<Pos2>) -> Vec<Pos2> {
     let mut points = points;
-    for i in 0..points.len() {
-        points[i] = Pos2 {
-            x: points[i].y,
-            y: -points[i].x,
+    for point in points.iter_mut() {
+        *point = Pos2 {
+            x: point.y,
+            y: -point.x,
         }
     }
     points
@@ -80,19 +82,19 @@ const CORNER_60: [Pos2; 2] = [Pos2 { x: 0., y: 1. }, Pos2 { x: 0.5, y: 0. }];
 fn get_corner_points(ctype: panes::CornerTypes) -> Vec<Pos2> {
     // CORNER_30.to_vec()
     match ctype {
-        panes::CornerTypes::SQUARE => CORNER_SQUARE.to_vec(),
+        panes::CornerTypes::Square => CORNER_SQUARE.to_vec(),
         panes::CornerTypes::Ang30 => CORNER_30.to_vec(),
         panes::CornerTypes::Ang45 => CORNER_45.to_vec(),
         panes::CornerTypes::Ang60 => CORNER_60.to_vec(),
     }
 }
 
-pub fn get_corners(rect: egui::Rect, corners: [panes::CornerTypes; 4]) -> Vec<Pos2> {
+pub fn get_corners(rect: egui::Rect, corners: [panes::CornerTypes; 4], theme: &Theme) -> Vec<Pos2> {
     let mut points: Vec<Pos2> = Vec::new();
-    let left = rect.left() + PANE_GAP;
-    let right = rect.right() - PANE_GAP;
-    let bottom = rect.bottom() - PANE_GAP;
-    let top = rect.top() + PANE_GAP;
+    let left = rect.left() + theme.pane_gap;
+    let right = rect.right() - theme.pane_gap;
+    let bottom = rect.bottom() - theme.pane_gap;
+    let top = rect.top() + theme.pane_gap;
 
     let rot_point_tl = get_corner_points(corners[0]).to_vec();
     let rot_point_tr = rotate_90(get_corner_points(corners[1]).to_vec());
@@ -100,36 +102,36 @@ pub fn get_corners(rect: egui::Rect, corners: [panes::CornerTypes; 4]) -> Vec<Po
     let rot_point_br = rotate_270(get_corner_points(corners[3]).to_vec());
 
     points.append(
-        add_Pos2(
+        add_pos2(
             Vec2 { x: left, y: top },
-            mult_Pos2(CORNER_CUT, rot_point_tl),
+            mult_pos2(theme.corner_cut(), rot_point_tl),
         )
         .as_mut(),
     );
 
     points.append(
-        add_Pos2(
+        add_pos2(
             Vec2 { x: right, y: top },
-            mult_Pos2(CORNER_CUT, rot_point_tr),
+            mult_pos2(theme.corner_cut(), rot_point_tr),
         )
         .as_mut(),
     );
 
     points.append(
-        add_Pos2(
+        add_pos2(
             Vec2 {
                 x: right,
                 y: bottom,
             },
-            mult_Pos2(CORNER_CUT, rot_point_bl),
+            mult_pos2(theme.corner_cut(), rot_point_bl),
         )
         .as_mut(),
     );
 
     points.append(
-        add_Pos2(
+        add_pos2(
             Vec2 { x: left, y: bottom },
-            mult_Pos2(CORNER_CUT, rot_point_br),
+            mult_pos2(theme.corner_cut(), rot_point_br),
         )
         .as_mut(),
     );
@@ -138,15 +140,16 @@ pub fn get_corners(rect: egui::Rect, corners: [panes::CornerTypes; 4]) -> Vec<Po
 
     // let filled_polygon =
 
-    return points;
+    points
 }
 
 pub fn update(
     wstate: MutexGuard<'_, structs::AuthState>,
-    ctx: &egui::Context,
-    frame: &mut eframe::Frame,
     ui: &mut egui::Ui,
     root_pane: &mut PaneInstance,
+    theme: &Theme,
+    show_ring: bool,
+    keyboard: &KeyboardStatus,
 ) {
     let rect: egui::Rect = ui.available_rect_before_wrap();
     // let ctx: cur_context = cur_context {
@@ -167,63 +170,198 @@ pub fn update(
     //     // windows,
     // );
 
-    // dots(painter, ui.clip_rect());
-    root_pane.render(ui.painter());
-    paint_password_circle(state, ctx, frame, ui, center, painter);
+    root_pane.render(ui.painter(), theme);
+    if let Some(banner) = &state.banner {
+        paint_banner(banner, rect, painter, theme);
+    }
+    if show_ring {
+        paint_password_circle(state, center, painter, &theme.login);
+        let error = match state.full {
+            true => Some("password too long"),
+            false => state.error.map(|error| error.message()),
+        };
+        if let Some(error) = error {
+            paint_error(error, center, painter, &theme.login);
+        }
+        paint_indicators(keyboard, center, painter, theme);
+    }
+}
+
+// The power menu over the whole screen, its lines centered
+pub fn paint_menu(lines: &[String], rect: Rect, painter: &egui::Painter, theme: &Theme) {
+    painter.rect_filled(rect, 0., theme.login.banner_fill);
+    painter.text(
+        rect.center(),
+        egui::Align2::CENTER_CENTER,
+        lines.join("\n"),
+        egui::FontId::monospace(theme.login.banner_font_size),
+        theme.text_color,
+    );
 }
 
-fn dots(painter: &egui::Painter, win_rect: egui::Rect) {
-    let dots_x = win_rect.right() / DOTS_SPACING;
-    let dots_y = win_rect.bottom() / DOTS_SPACING;
-    for x in (-dots_x as i32 / 2)..((dots_x as i32 / 2) + 1) {
-        for y in (-dots_y as i32 / 2)..((dots_y as i32 / 2) + 1) {
-            painter.circle(
-                Pos2 {
-                    x: win_rect.right() / 2. + (x as f32 * DOTS_SPACING),
-                    y: win_rect.bottom() / 2. + (y as f32 * DOTS_SPACING),
-                },
-                DOTS_RAD,
-                DOTS_COLOR,
-                egui::Stroke::NONE,
+// A message from the control socket across the top of the screen
+fn paint_banner(text: &str, rect: Rect, painter: &egui::Painter, theme: &Theme) {
+    let galley = painter.layout(
+        text.to_string(),
+        egui::FontId::proportional(theme.login.banner_font_size),
+        theme.text_color,
+        rect.width() * 0.8,
+    );
+    let padding = theme.login.banner_font_size / 2.;
+    let size = galley.size() + Vec2::splat(2. * padding);
+    let frame = Rect::from_min_size(
+        Pos2::new(
+            rect.center().x - size.x / 2.,
+            rect.top() + theme.pane_gap + padding,
+        ),
+        size,
+    );
+    painter.rect_filled(frame, 0., theme.login.banner_fill);
+    painter.galley(frame.min + Vec2::splat(padding), galley, theme.text_color);
+}
+
+// Why the password couldn't be checked or typed, one line above the ring
+fn paint_error(error: &str, center: Pos2, painter: &egui::Painter, theme: &LoginTheme) {
+    let bottom = center.y - theme.circle_radius - theme.indicator_font_size;
+    painter.text(
+        Pos2::new(center.x, bottom),
+        egui::Align2::CENTER_BOTTOM,
+        error,
+        egui::FontId::monospace(theme.indicator_font_size),
+        theme.warning_color,
+    );
+}
+
+// Caps Lock, Num Lock and the layout, one line under the ring
+fn paint_indicators(
+    keyboard: &KeyboardStatus,
+    center: Pos2,
+    painter: &egui::Painter,
+    theme: &Theme,
+) {
+    let font = egui::FontId::monospace(theme.login.indicator_font_size);
+    let mut parts: Vec<(String, Color32)> = Vec::new();
+    if keyboard.caps_lock {
+        parts.push(("CAPS LOCK".to_string(), theme.login.warning_color));
+    }
+    if keyboard.num_lock {
+        parts.push(("NUM".to_string(), theme.text_color));
+    }
+    if let Some(layout) = &keyboard.layout {
+        parts.push((layout.clone(), theme.text_color));
+    }
+    if parts.is_empty() {
+        return;
+    }
+
+    let mut job = egui::text::LayoutJob::default();
+    for (i, (text, color)) in parts.into_iter().enumerate() {
+        if i > 0 {
+            job.append(
+                "  ",
+                0.,
+                egui::TextFormat::simple(font.clone(), theme.text_color),
             );
         }
+        job.append(&text, 0., egui::TextFormat::simple(font.clone(), color));
     }
+    let galley = painter.layout_job(job);
+    let top = center.y + theme.login.circle_radius + theme.login.indicator_font_size;
+    let pos = Pos2::new(center.x - galley.size().x / 2., top);
+    painter.galley(pos, galley, theme.text_color);
 }
 
 fn paint_password_circle(
     state: &structs::AuthState,
 
-    ctx: &egui::Context,
-    frame: &eframe::Frame,
-    ui: &egui::Ui,
-
     center: egui::Pos2,
     painter: &egui::Painter,
+    theme: &LoginTheme,
 ) {
-    let len = state.password.len();
+    let start_angle = theme.start_angle.to_radians();
+
+    // The old dots fall into the middle and fade out
+    if let Some((progress, dots)) = state.clear_progress() {
+        let fade = 1. - progress;
+        let ang_per_dot = 2. * PI / dots as f32;
+        for i in 0..dots {
+            let pos = rot_circle(
+                i as i16,
+                center,
+                theme.circle_radius * fade,
+                start_angle,
+                ang_per_dot,
+            );
+            painter.circle(
+                pos,
+                theme.dot_radius * fade,
+                theme.dot_fill.gamma_multiply(fade),
+                Stroke::new(
+                    theme.dot_stroke.width,
+                    theme.dot_stroke.color.gamma_multiply(fade),
+                ),
+            );
+        }
+    }
+
+    paint_phase(state.phase, center, painter, theme);
 
-    if state.failed_attempts > 0 {
+    if let Some((left, length)) = state.grace_left() {
+        paint_arc(
+            painter,
+            center,
+            theme.circle_radius,
+            start_angle,
+            left.as_secs_f32() / length.as_secs_f32(),
+            theme.grace_stroke,
+        );
+    }
+
+    if state.failed_attempts > 0 || matches!(state.phase, structs::AuthPhase::Error(_)) {
         painter.circle(
             center,
-            LOGIN_CIRCLE_RADIUS - LOGIN_FAIL_CIRCLE_STROKE.width,
+            theme.circle_radius - theme.fail_ring.width,
             Color32::TRANSPARENT,
-            LOGIN_FAIL_CIRCLE_STROKE,
+            theme.fail_ring,
         );
     }
 
-    let ang_per_char = 2. * PI / state.password.len() as f32;
+    let ang_per_char = 2. * PI / state.password.char_count() as f32;
     let ang_per_fail = 2. * PI / state.failed_attempts as f32;
-    let len: i16 = state.password.len() as i16;
+    let len: i16 = state.password.char_count() as i16;
 
     let mut last_pos = rot_circle(
         len - 1,
         center,
-        LOGIN_CIRCLE_RADIUS,
-        LOGIN_SUBCIRCLE_START_ANG,
+        theme.circle_radius,
+        start_angle,
         ang_per_char,
     );
 
+    // Counts down in the middle of the ring, where the failures usually go
+    let attempts = state.attempts();
+    if let (Some(left), Some((_, length))) = (attempts.remaining(), attempts.lockout) {
+        paint_arc(
+            painter,
+            center,
+            theme.circle_radius,
+            start_angle,
+            left.as_secs_f32() / length.as_secs_f32().max(f32::EPSILON),
+            theme.lockout_stroke,
+        );
+        painter.text(
+            center,
+            egui::Align2::CENTER_CENTER,
+            format!("{}", left.as_secs() + 1),
+            egui::FontId::monospace(theme.countdown_font_size),
+            theme.lockout_stroke.color,
+        );
+    }
+
     for i in 0..state.failed_attempts {
+        if attempts.locked_out() {
+            break;
+        }
         let pos: egui::Pos2 = {
             if state.failed_attempts <= 1 {
                 center
@@ -231,8 +369,8 @@ fn paint_password_circle(
                 rot_circle(
                     i as i16,
                     center,
-                    LOGIN_FAIL_COUNT_CIRCLE_RADIUS,
-                    LOGIN_SUBCIRCLE_START_ANG,
+                    theme.fail_count_radius,
+                    start_angle,
                     ang_per_fail,
                 )
             }
@@ -240,9 +378,9 @@ fn paint_password_circle(
 
         painter.circle(
             pos,
-            LOGIN_SUBCIRCLE_RADIUS,
-            LOGIN_FAIL_COUNT_CIRCLE_COLOR,
-            LOGIN_FAIL_COUNT_CIRCLE_STROKE,
+            theme.dot_radius,
+            theme.fail_dot_fill,
+            theme.fail_dot_stroke,
         );
     }
 
@@ -251,31 +389,85 @@ fn paint_password_circle(
             if len <= 1 {
                 center
             } else {
-                rot_circle(
-                    i,
-                    center,
-                    LOGIN_CIRCLE_RADIUS,
-                    LOGIN_SUBCIRCLE_START_ANG,
-                    ang_per_char,
-                )
+                rot_circle(i, center, theme.circle_radius, start_angle, ang_per_char)
             }
         };
 
-        painter.circle(
-            pos,
-            LOGIN_SUBCIRCLE_RADIUS,
-            LOGIN_SUBCIRCLE_COLOR,
-            LOGIN_SUBCIRCLE_STROKE,
-        );
+        painter.circle(pos, theme.dot_radius, theme.dot_fill, theme.dot_stroke);
 
         if len > 1 {
-            painter.line_segment([last_pos, pos], LOGIN_CIRCLE_LINE_STROKE);
+            painter.line_segment([last_pos, pos], theme.line_stroke);
 
             last_pos = pos;
         }
     }
+}
 
-    ctx.request_repaint();
+// A spinner while the password is checked, then a flash for the verdict:
+// the ring closes on success and lights up on a failure or an error
+fn paint_phase(
+    phase: structs::AuthPhase,
+    center: Pos2,
+    painter: &egui::Painter,
+    theme: &LoginTheme,
+) {
+    let start_angle = theme.start_angle.to_radians();
+    if let structs::AuthPhase::Verifying(since) = phase {
+        let turns = since.elapsed().as_secs_f32() / SPINNER_PERIOD;
+        paint_arc(
+            painter,
+            center,
+            theme.circle_radius,
+            start_angle + 2. * PI * turns.fract(),
+            SPINNER_LENGTH,
+            theme.verifying_stroke,
+        );
+        return;
+    }
+
+    let Some(progress) = phase.result_progress() else {
+        return;
+    };
+    let flash = |color: Color32| {
+        painter.circle_filled(
+            center,
+            theme.circle_radius,
+            color.gamma_multiply(0.4 * (1. - progress)),
+        );
+    };
+    match phase {
+        structs::AuthPhase::Succeeded(_) => paint_arc(
+            painter,
+            center,
+            theme.circle_radius,
+            start_angle,
+            (2. * progress).min(1.),
+            theme.success_stroke,
+        ),
+        structs::AuthPhase::Failed(_) => flash(theme.fail_ring.color),
+        structs::AuthPhase::Error(_) => flash(theme.warning_color),
+        structs::AuthPhase::Idle | structs::AuthPhase::Verifying(_) => {}
+    }
+}
+
+// `fraction` of the circle, clockwise from `start_angle`
+fn paint_arc(
+    painter: &egui::Painter,
+    center: Pos2,
+    radius: f32,
+    start_angle: f32,
+    fraction: f32,
+    stroke: Stroke,
+) {
+    let fraction = fraction.clamp(0., 1.);
+    let steps = (64. * fraction).ceil().max(1.) as usize;
+    let points = (0..=steps)
+        .map(|i| {
+            let angle = start_angle + 2. * PI * fraction * i as f32 / steps as f32;
+            center + radius * Vec2::angled(angle)
+        })
+        .collect();
+    painter.add(egui::Shape::line(points, stroke));
 }
 
 pub fn find_largest_rectangle(points: &[Pos2]) -> Option<Rect> {
@@ -326,7 +518,7 @@ pub fn find_largest_rectangle(points: &[Pos2]) -> Option<Rect> {
     // Helper function to check if a set of points forms a valid rectangle
     fn is_valid_rectangle(p1: &Pos2, p2: &Pos2, p3: &Pos2, p4: &Pos2) -> bool {
         // Sort points by x coordinate
-        let mut pts = vec![p1, p2, p3, p4];
+        let mut pts = [p1, p2, p3, p4];
         pts.sort_by(|a, b| a.x.partial_cmp(&b.x).unwrap());
 
         // For a valid rectangle:
@@ -334,8 +526,6 @@ pub fn find_largest_rectangle(points: &[Pos2]) -> Option<Rect> {
         // - The last two points should have the same x-coordinate
         // - Two points should have the min y-coordinate
         // - Two points should have the max y-coordinate
-        let x_eps = 0.;
-        let y_eps = 0.;
 
         // // Check x-coordinates are properly paired
         // if (pts[0].x - pts[1].x).abs() >= x_eps || (pts[2].x - pts[3].x).abs() >= x_eps {
@@ -0,0 +1,166 @@
+//! Locks a headless sway with two outputs and unlocks it again. Skipped
+//! when sway isn't installed.
+
+use std::fs;
+use std::io::Read;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const TIMEOUT: Duration = Duration::from_secs(20);
+
+// Kills whatever is left when the test ends, passing or not
+struct Killed(Child);
+
+impl Drop for Killed {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+fn has_sway() -> bool {
+    Command::new("sway")
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("raylock-test-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    Command::new("chmod").arg("700").arg(&dir).status().unwrap();
+    dir
+}
+
+fn wait_for(what: &str, mut done: impl FnMut() -> bool) {
+    let deadline = Instant::now() + TIMEOUT;
+    while !done() {
+        assert!(Instant::now() < deadline, "timed out waiting for {}", what);
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+fn start_sway(runtime: &Path) -> (Killed, String) {
+    let sway = Command::new("sway")
+        .args(["-c", "/dev/null"])
+        .env("XDG_RUNTIME_DIR", runtime)
+        .env("WLR_BACKENDS", "headless")
+        .env("WLR_HEADLESS_OUTPUTS", "2")
+        .env("WLR_LIBINPUT_NO_DEVICES", "1")
+        .env_remove("WAYLAND_DISPLAY")
+        .env_remove("SWAYSOCK")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let mut display = None;
+    wait_for("sway's wayland socket", || {
+        display = fs::read_dir(runtime).unwrap().flatten().find_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            (name.starts_with("wayland-") && !name.ends_with(".lock")).then_some(name)
+        });
+        display.is_some()
+    });
+    (Killed(sway), display.unwrap())
+}
+
+// A raylock locking the session, which writes to `ready` once every output
+// shows its lock surface
+fn start_raylock(
+    runtime: &Path,
+    config: &Path,
+    display: &str,
+    extra: &[&str],
+) -> (Killed, fs::File) {
+    let mut fds = [0; 2];
+    assert_eq!(unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) }, 0);
+    let (reader, writer) = unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+    let writer_fd = writer.as_raw_fd();
+    let mut command = Command::new(env!("CARGO_BIN_EXE_raylock"));
+    command
+        .args([
+            "--mode",
+            "session-lock",
+            "--unlock-signal",
+            "--ready-fd",
+            "3",
+        ])
+        .args(extra)
+        .env("XDG_RUNTIME_DIR", runtime)
+        .env("XDG_CONFIG_HOME", config)
+        .env("XDG_STATE_HOME", config)
+        .env("WAYLAND_DISPLAY", display)
+        .env_remove("SWAYSOCK");
+    unsafe {
+        command.pre_exec(move || {
+            if libc::dup2(writer_fd, 3) == -1 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    let child = command.spawn().unwrap();
+    drop(writer);
+    (Killed(child), fs::File::from(reader))
+}
+
+fn wait_ready(ready: &mut fs::File) -> bool {
+    let mut byte = [0u8; 1];
+    matches!(ready.read(&mut byte), Ok(1))
+}
+
+fn wait_exit(raylock: &mut Killed) -> ExitStatus {
+    let mut status = None;
+    wait_for("raylock to exit", || {
+        status = raylock.0.try_wait().unwrap();
+        status.is_some()
+    });
+    status.unwrap()
+}
+
+#[test]
+fn locks_every_output_and_unlocks() {
+    if !has_sway() {
+        eprintln!("skipping: sway is not installed");
+        return;
+    }
+    let runtime = temp_dir("runtime");
+    let config = temp_dir("config");
+    // Idle power off goes through sway IPC, which isn't wanted here
+    fs::create_dir_all(config.join("raylock")).unwrap();
+    fs::write(
+        config.join("raylock/config.toml"),
+        "[idle]\ndim_after_secs = 0\npower_off_after_secs = 0\n",
+    )
+    .unwrap();
+    let (_sway, display) = start_sway(&runtime);
+
+    // Ready only comes once the lock is granted and both outputs have a
+    // drawn lock surface, raylock exits instead if either is missing
+    let (mut raylock, mut ready) = start_raylock(&runtime, &config, &display, &[]);
+    assert!(wait_ready(&mut ready), "raylock never reported the lock");
+
+    // The compositor takes one lock at a time. --force gets the second one
+    // past raylock's own lock file and as far as asking sway.
+    let (mut second, _) = start_raylock(&runtime, &config, &display, &["--force"]);
+    assert!(!wait_exit(&mut second).success());
+
+    unsafe { libc::kill(raylock.0.id() as libc::pid_t, libc::SIGUSR1) };
+    assert!(wait_exit(&mut raylock).success());
+
+    // Only possible once the first lock was given back
+    let (mut relock, mut ready) = start_raylock(&runtime, &config, &display, &["--force"]);
+    assert!(wait_ready(&mut ready), "the session stayed locked");
+    unsafe { libc::kill(relock.0.id() as libc::pid_t, libc::SIGUSR1) };
+    assert!(wait_exit(&mut relock).success());
+
+    let _ = fs::remove_dir_all(&runtime);
+    let _ = fs::remove_dir_all(&config);
+}